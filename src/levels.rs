@@ -1,7 +1,10 @@
-use super::{MAX_PLAYER_SPEED, PIXEL_SIZE, PLAYER_ACCEL, TILE_PIXELS, TILE_SIZE};
+use super::{
+    BOUNCY_SAW_BOUNCES, BOUNCY_SAW_FRAMES, COYOTE_FRAMES, JUMP_BUFFER_FRAMES, MAX_PLAYER_SPEED,
+    PIXEL_SIZE, PLAYER_ACCEL, TILE_PIXELS, TILE_SIZE, WALL_COYOTE_FRAMES,
+};
 use crate::{
-    sound_cache, texture_cache, Adjacencies, AdvancedAdjacencies, Theme, TransitionAnimationType,
-    SCREEN_HEIGHT, SCREEN_WIDTH,
+    levelset_path, pack, sound_cache, texture_cache, tiled, Adjacencies, AdvancedAdjacencies,
+    Theme, TransitionAnimationType, SCREEN_HEIGHT, SCREEN_WIDTH,
 };
 use macroquad::audio::{play_sound, PlaySoundParams, Sound};
 use macroquad::prelude::*;
@@ -11,20 +14,148 @@ fn draw_rect_i32(x: i32, y: i32, w: i32, h: i32, c: Color) {
     draw_rectangle(x as f32, y as f32, w as f32, h as f32, c)
 }
 
+// how a kind of wall behaves to stand on and to grab
+#[derive(Copy, Clone, Default, PartialEq, Eq, Debug)]
+pub struct Surface {
+    // low friction when standing on it
+    pub ice: bool,
+    // can't be wall slid on
+    pub nogrip: bool,
+    // slower wall slides and stronger wall jumps
+    pub sticky: bool,
+}
+
+impl Surface {
+    pub fn from_string(s: &str) -> Self {
+        let mut surface = Surface::default();
+        for word in s.split_whitespace() {
+            match word {
+                "ice" => surface.ice = true,
+                "nogrip" => surface.nogrip = true,
+                "sticky" => surface.sticky = true,
+                _ => (),
+            }
+        }
+        surface
+    }
+}
+
+// speeds and accelerations are stored in subpixels, frame counts in ticks
+#[derive(Copy, Clone)]
+pub struct Physics {
+    pub max_speed: i32,
+    pub accel: i32,
+    pub gravity: i32,
+    pub jump_gravity: i32,
+    pub fast_gravity: i32,
+    pub jump_velocity: i32,
+    pub wall_jump_vx: i32,
+    pub wall_jump_vy: i32,
+    pub wall_jump_freeze: i32,
+    pub coyote_frames: i32,
+    pub wall_coyote_frames: i32,
+    pub jump_buffer_frames: i32,
+}
+
+impl Default for Physics {
+    fn default() -> Self {
+        Physics {
+            max_speed: MAX_PLAYER_SPEED,
+            accel: PLAYER_ACCEL,
+            gravity: TILE_SIZE / 16 / 2,
+            jump_gravity: TILE_SIZE / 16 / 5,
+            fast_gravity: TILE_SIZE / 16,
+            jump_velocity: TILE_SIZE * 5 / 16,
+            wall_jump_vx: TILE_SIZE * 5 / 16,
+            wall_jump_vy: TILE_SIZE * 4 / 16,
+            wall_jump_freeze: 14,
+            coyote_frames: COYOTE_FRAMES,
+            wall_coyote_frames: WALL_COYOTE_FRAMES,
+            jump_buffer_frames: JUMP_BUFFER_FRAMES,
+        }
+    }
+}
+
+impl Physics {
+    // same `key: value` lines as themes, speeds are written in pixels per tick
+    pub fn from_path(path: &str) -> Self {
+        let s = pack::read_to_string(path).expect("physics should exist");
+        let s = s.trim().replace("\r\n", "\n");
+
+        let mut physics = Physics::default();
+
+        for line in s.lines() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let mut parts = line.split(": ");
+            let (a, b) = (
+                parts.next().expect("should exist").trim(),
+                parts.next().expect("should exist").trim(),
+            );
+            let pixels = || {
+                (b.parse::<f32>().expect("physics values should be numbers") * PIXEL_SIZE as f32)
+                    as i32
+            };
+            let ticks = || {
+                b.parse::<i32>()
+                    .expect("frame counts should be whole numbers")
+            };
+            match a {
+                "max_speed" => physics.max_speed = pixels(),
+                "accel" => physics.accel = pixels().max(1),
+                "gravity" => physics.gravity = pixels(),
+                "jump_gravity" => physics.jump_gravity = pixels(),
+                "fast_gravity" => physics.fast_gravity = pixels(),
+                "jump_velocity" => physics.jump_velocity = pixels(),
+                "wall_jump_vx" => physics.wall_jump_vx = pixels(),
+                "wall_jump_vy" => physics.wall_jump_vy = pixels(),
+                "wall_jump_freeze" => physics.wall_jump_freeze = ticks(),
+                "coyote_frames" => physics.coyote_frames = ticks(),
+                "wall_coyote_frames" => physics.wall_coyote_frames = ticks(),
+                "jump_buffer_frames" => physics.jump_buffer_frames = ticks(),
+                _ => (),
+            }
+        }
+
+        physics
+    }
+}
+
 pub struct GlobalState {
     pub changed_tiles: HashMap<(usize, usize, usize, usize), Tile>,
     pub keys: [i32; 6],
     pub timer: i32,
     pub secrets: i32,
+    pub coins: i32,
     pub jumps: i32,
     pub collected_jump_arrows: VecDeque<(usize, usize, usize)>,
     pub binocularing: bool,
     pub binocular_t: i32,
     pub binocular_rx: i32,
     pub binocular_ry: i32,
+    // sign and page being read
+    pub reading: Option<(usize, usize)>,
     pub modifiers: Modifiers,
     pub default_modifiers: Modifiers,
+    // the defaults plus whatever the current room forces, what timed pickups wear off to
+    pub room_modifiers: Modifiers,
+    // pickup, ticks left, ticks total
+    pub modifier_timers: Vec<(Pickup, i32, i32)>,
     pub switched: bool,
+    pub focus: (i32, i32),
+    pub physics: Physics,
+    // surface of wall, wall2, wall3 and wall4 in the current room's theme
+    pub surfaces: [Surface; 4],
+    // stalactite sprite and shatter frames for the current room's theme
+    pub stalactite_sprites: (String, String),
+    pub lives: i32,
+    pub checkpoint: Option<usize>,
+    pub game_over: bool,
+    // ticks left in a challenge room
+    pub challenge_timer: Option<i32>,
+    // rooms the player has been in, for the map
+    pub visited: Vec<usize>,
 }
 
 #[derive(Copy, Clone)]
@@ -38,6 +169,9 @@ pub struct Modifiers {
     pub uncapped_speed: bool,
     pub infinitejumps: bool,
     pub unkillable: bool,
+    // 0 means lives are off
+    pub lives: i32,
+    pub hardcore: bool,
 }
 
 impl Default for Modifiers {
@@ -52,6 +186,8 @@ impl Default for Modifiers {
             uncapped_speed: false,
             infinitejumps: false,
             unkillable: false,
+            lives: 0,
+            hardcore: false,
         }
     }
 }
@@ -90,28 +226,185 @@ impl Modifiers {
         if self.unkillable {
             imgs.push("assets/unkillable.png")
         }
+        if self.hardcore {
+            imgs.push("assets/hardcore.png")
+        }
         imgs
     }
+
+    fn apply(&mut self, p: Pickup) {
+        match p {
+            Pickup::SuperSlippery => self.superslippery = true,
+            Pickup::InvisibleLevel => self.invisiblelevel = true,
+            Pickup::InvisiblePlayer => self.invisibleplayer = true,
+            Pickup::NoWallJump => self.nowalljump = true,
+            Pickup::AlwaysJumping => self.alwaysjumping = true,
+            Pickup::UncappedSpeed => self.uncapped_speed = true,
+            Pickup::InfiniteJumps => self.infinitejumps = true,
+            Pickup::Unkillable => self.unkillable = true,
+            Pickup::FastForward => self.game_speed = 1.5,
+            Pickup::Rewind => self.game_speed = 0.5,
+        }
+    }
+
+    // put back whatever the room started with
+    fn revert(&mut self, p: Pickup, default: &Modifiers) {
+        match p {
+            Pickup::SuperSlippery => self.superslippery = default.superslippery,
+            Pickup::InvisibleLevel => self.invisiblelevel = default.invisiblelevel,
+            Pickup::InvisiblePlayer => self.invisibleplayer = default.invisibleplayer,
+            Pickup::NoWallJump => self.nowalljump = default.nowalljump,
+            Pickup::AlwaysJumping => self.alwaysjumping = default.alwaysjumping,
+            Pickup::UncappedSpeed => self.uncapped_speed = default.uncapped_speed,
+            Pickup::InfiniteJumps => self.infinitejumps = default.infinitejumps,
+            Pickup::Unkillable => self.unkillable = default.unkillable,
+            Pickup::FastForward | Pickup::Rewind => self.game_speed = default.game_speed,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Pickup {
+    SuperSlippery,
+    InvisibleLevel,
+    InvisiblePlayer,
+    NoWallJump,
+    AlwaysJumping,
+    UncappedSpeed,
+    InfiniteJumps,
+    Unkillable,
+    FastForward,
+    Rewind,
+}
+
+impl Pickup {
+    // accepts both the icon names and the Modifiers field names
+    fn from_name(s: &str) -> Option<Self> {
+        match s {
+            "icecube" | "superslippery" => Some(Self::SuperSlippery),
+            "hiddenlevel" | "invisiblelevel" => Some(Self::InvisibleLevel),
+            "playervanish" | "invisibleplayer" => Some(Self::InvisiblePlayer),
+            "nowalljump" => Some(Self::NoWallJump),
+            "alwaysjumping" => Some(Self::AlwaysJumping),
+            "uncappedspeed" | "uncapped_speed" => Some(Self::UncappedSpeed),
+            "infinitejumps" => Some(Self::InfiniteJumps),
+            "unkillable" => Some(Self::Unkillable),
+            "fastforward" => Some(Self::FastForward),
+            "rewind" => Some(Self::Rewind),
+            _ => None,
+        }
+    }
+    // same icons as Modifiers::get_images
+    pub fn image(&self) -> &'static str {
+        match self {
+            Self::SuperSlippery => "assets/icecube.png",
+            Self::InvisibleLevel => "assets/hiddenlevel.png",
+            Self::InvisiblePlayer => "assets/playervanish.png",
+            Self::NoWallJump => "assets/nowalljump.png",
+            Self::AlwaysJumping => "assets/alwaysjumping.png",
+            Self::UncappedSpeed => "assets/uncappedspeed.png",
+            Self::InfiniteJumps => "assets/infinitejumps.png",
+            Self::Unkillable => "assets/unkillable.png",
+            Self::FastForward => "assets/fastforward.png",
+            Self::Rewind => "assets/rewind.png",
+        }
+    }
 }
 
 impl GlobalState {
-    pub fn new(mods: Option<Modifiers>) -> Self {
+    pub fn new(mods: Option<Modifiers>, physics: Physics) -> Self {
         GlobalState {
             changed_tiles: HashMap::new(),
             keys: [0; 6],
             timer: 0,
             secrets: 0,
+            coins: 0,
             jumps: 0,
             collected_jump_arrows: VecDeque::new(),
             binocularing: false,
             binocular_t: 0,
             binocular_rx: 0,
             binocular_ry: 0,
+            reading: None,
             modifiers: mods.unwrap_or_default(),
             default_modifiers: mods.unwrap_or_default(),
+            room_modifiers: mods.unwrap_or_default(),
+            modifier_timers: vec![],
             switched: false,
+            focus: (0, 0),
+            physics,
+            surfaces: Theme::default().surfaces(),
+            stalactite_sprites: Theme::default().stalactite_sprites(),
+            lives: mods.unwrap_or_default().lives,
+            checkpoint: None,
+            game_over: false,
+            challenge_timer: None,
+            visited: vec![],
+        }
+    }
+    // returns the room to restart from once the last life is gone
+    pub fn lose_life(&mut self) -> Option<usize> {
+        if self.default_modifiers.hardcore {
+            self.game_over = true;
+            return None;
+        }
+        if self.default_modifiers.lives == 0 {
+            return None;
+        }
+        self.lives -= 1;
+        if self.lives > 0 {
+            return None;
+        }
+        self.lives = self.default_modifiers.lives;
+        Some(self.checkpoint.unwrap_or(0))
+    }
+    // the parts of the state that follow the player into another levelset
+    pub fn carry_over(&mut self, other: &GlobalState) {
+        self.timer = other.timer;
+        self.lives = other.lives;
+        self.game_over = other.game_over;
+        self.default_modifiers = other.default_modifiers;
+    }
+    pub fn reset_modifiers(&mut self) {
+        self.modifiers = self.default_modifiers;
+        self.modifier_timers.clear();
+    }
+    pub fn enter_room(&mut self, level: &Level, theme: &Theme) {
+        self.surfaces = theme.surfaces();
+        self.stalactite_sprites = theme.stalactite_sprites();
+        self.jumps = 0;
+        self.collected_jump_arrows = VecDeque::new();
+        self.reset_modifiers();
+        for p in level.modifiers.iter() {
+            self.modifiers.apply(*p);
+        }
+        self.room_modifiers = self.modifiers;
+        self.challenge_timer = level.time_limit;
+        self.reading = None;
+    }
+    fn collect_pickup(&mut self, p: Pickup, duration: i32) {
+        self.modifiers.apply(p);
+        // rewind and fastforward share game_speed, so they cancel each other
+        self.modifier_timers.retain(|(o, _, _)| {
+            *o != p
+                && !matches!(
+                    (o, p),
+                    (Pickup::Rewind, Pickup::FastForward) | (Pickup::FastForward, Pickup::Rewind)
+                )
+        });
+        if duration > 0 {
+            self.modifier_timers.push((p, duration, duration));
         }
     }
+    fn tick_modifiers(&mut self) {
+        for (p, left, _) in self.modifier_timers.iter_mut() {
+            *left -= 1;
+            if *left <= 0 {
+                self.modifiers.revert(*p, &self.room_modifiers);
+            }
+        }
+        self.modifier_timers.retain(|(_, left, _)| *left > 0);
+    }
 }
 
 #[derive(PartialEq, Eq, Copy, Clone, Debug)]
@@ -136,6 +429,14 @@ impl Direction {
             Self::Down
         }
     }
+    fn opposite(&self) -> Self {
+        match self {
+            Self::Up => Self::Down,
+            Self::Down => Self::Up,
+            Self::Left => Self::Right,
+            Self::Right => Self::Left,
+        }
+    }
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
@@ -147,6 +448,15 @@ pub enum Tile {
     Wall2,
     Wall3,
     Wall4,
+    Breakable,
+
+    // solid, but only in part of the tile
+    HalfBlockTop,
+    HalfBlockBottom,
+    HalfBlockLeft,
+    HalfBlockRight,
+    Ledge,
+    Pillar,
 
     BackWall,
     BackWall2,
@@ -161,12 +471,29 @@ pub enum Tile {
     ExitAnchor,
 
     Spikes,
+    // cycle length and phase offset, in ticks
+    TimedSpikes(i32, i32),
+    // named after the direction the points face
+    SpikesUp,
+    SpikesDown,
+    SpikesLeft,
+    SpikesRight,
 
     OneWayLeft,
     OneWayRight,
     OneWayUp,
     OneWayDown,
 
+    // floor slopes, named after the way they rise. they aren't solid,
+    // the player's feet get snapped onto them instead
+    SlopeUpRight,
+    SlopeUpLeft,
+    // 22.5 degree slopes are two tiles wide
+    GentleSlopeUpRightLow,
+    GentleSlopeUpRightHigh,
+    GentleSlopeUpLeftLow,
+    GentleSlopeUpLeftHigh,
+
     RedKey,
     YellowKey,
     GreenKey,
@@ -191,7 +518,20 @@ pub enum Tile {
     SlowSawLauncherUp,
     SlowSawLauncherDown,
 
+    BouncySawLauncherLeft,
+    BouncySawLauncherRight,
+    BouncySawLauncherUp,
+    BouncySawLauncherDown,
+
+    Turret,
+    Stalactite,
+
     Secret,
+    ChallengeSecret,
+    FailGate,
+    Coin,
+    ExtraLife,
+    Checkpoint,
     Goal,
 
     JumpArrow,
@@ -199,12 +539,22 @@ pub enum Tile {
 
     Binocular,
 
+    SignGeneric,
+    Sign(usize),
+
     Switch,
     SwitchRed,
     SwitchBlue,
 
-    IceCube,
-    PlayerVanish,
+    // duration in ticks, 0 lasts until the next room
+    Pickup(Pickup, i32),
+}
+
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum SpikeState {
+    Retracted,
+    Warning,
+    Extended,
 }
 
 fn tilemap_draw(t: &Texture2D, x: i32, y: i32, touching: &Adjacencies) {
@@ -262,10 +612,39 @@ fn adv_tilemap_draw(t: &Texture2D, x: i32, y: i32, touching: &AdvancedAdjacencie
 }
 
 impl Tile {
+    // the part of the tile that's actually there, for tiles that don't fill their square
+    fn collision_shape(&self, my_aabb: AABB) -> AABB {
+        let (hw, hh) = (my_aabb.w / 2, my_aabb.h / 2);
+        match self {
+            Self::HalfBlockTop => AABB { h: hh, ..my_aabb },
+            Self::HalfBlockBottom => AABB {
+                y: my_aabb.y + hh,
+                h: hh,
+                ..my_aabb
+            },
+            Self::HalfBlockLeft => AABB { w: hw, ..my_aabb },
+            Self::HalfBlockRight => AABB {
+                x: my_aabb.x + hw,
+                w: hw,
+                ..my_aabb
+            },
+            Self::Ledge => AABB {
+                h: my_aabb.h / 4,
+                ..my_aabb
+            },
+            Self::Pillar => AABB {
+                x: my_aabb.x + my_aabb.w * 3 / 8,
+                w: my_aabb.w / 4,
+                ..my_aabb
+            },
+            _ => my_aabb,
+        }
+    }
+
     pub fn is_solid(
         &self,
         b_box: AABB,
-        _c_box: AABB,
+        c_box: AABB,
         my_aabb: AABB,
         direction: Direction,
         gs: &GlobalState,
@@ -275,6 +654,15 @@ impl Tile {
             Self::Wall2 => true,
             Self::Wall3 => true,
             Self::Wall4 => true,
+            Self::Breakable => true,
+            Self::FailGate => true,
+
+            Self::HalfBlockTop
+            | Self::HalfBlockBottom
+            | Self::HalfBlockLeft
+            | Self::HalfBlockRight
+            | Self::Ledge
+            | Self::Pillar => self.collision_shape(my_aabb).intersect(&c_box),
 
             // note. the directions are seemingly reversed here
             // this is because "left" refers to it being on the left of the block
@@ -292,6 +680,24 @@ impl Tile {
             Self::MagentaLock => gs.keys[5] == 0,
 
             Self::Spikes => gs.modifiers.unkillable,
+            Self::TimedSpikes(..) => gs.modifiers.unkillable && self.is_deadly(gs),
+            // directional spikes are solid from behind, like a one way
+            Self::SpikesUp => {
+                gs.modifiers.unkillable
+                    || ((b_box.y >= my_aabb.y + my_aabb.h) && direction == Direction::Up)
+            }
+            Self::SpikesDown => {
+                gs.modifiers.unkillable
+                    || ((b_box.y + b_box.h <= my_aabb.y) && direction == Direction::Down)
+            }
+            Self::SpikesLeft => {
+                gs.modifiers.unkillable
+                    || ((b_box.x >= my_aabb.x + my_aabb.w) && direction == Direction::Left)
+            }
+            Self::SpikesRight => {
+                gs.modifiers.unkillable
+                    || ((b_box.x + b_box.w <= my_aabb.x) && direction == Direction::Right)
+            }
 
             Self::Switch => true,
             Self::SwitchRed => !gs.switched,
@@ -301,19 +707,109 @@ impl Tile {
         }
     }
 
-    pub fn is_deadly(&self) -> bool {
+    fn surface(&self, gs: &GlobalState) -> Surface {
+        match self {
+            Self::Wall => gs.surfaces[0],
+            Self::Wall2 => gs.surfaces[1],
+            Self::Wall3 => gs.surfaces[2],
+            Self::Wall4 => gs.surfaces[3],
+            _ => Surface::default(),
+        }
+    }
+
+    // height of a slope's surface at its left and right edges, from the bottom of the tile
+    fn slope_ends(&self) -> Option<(i32, i32)> {
+        match self {
+            Self::SlopeUpRight => Some((0, TILE_SIZE)),
+            Self::SlopeUpLeft => Some((TILE_SIZE, 0)),
+            Self::GentleSlopeUpRightLow => Some((0, TILE_SIZE / 2)),
+            Self::GentleSlopeUpRightHigh => Some((TILE_SIZE / 2, TILE_SIZE)),
+            Self::GentleSlopeUpLeftLow => Some((TILE_SIZE / 2, 0)),
+            Self::GentleSlopeUpLeftHigh => Some((TILE_SIZE, TILE_SIZE / 2)),
+            _ => None,
+        }
+    }
+
+    fn slope_height(&self, local_x: i32) -> Option<i32> {
+        self.slope_ends()
+            .map(|(l, r)| l + (r - l) * local_x / TILE_SIZE)
+    }
+
+    pub fn is_deadly(&self, gs: &GlobalState) -> bool {
         match self {
             Self::Spikes => true,
+            Self::TimedSpikes(..) => self.spike_state(gs) == SpikeState::Extended,
+            Self::SpikesUp | Self::SpikesDown | Self::SpikesLeft | Self::SpikesRight => true,
             _ => false,
         }
     }
 
+    // the part of the tile that actually kills, only the pointy half for directional spikes
+    fn deadly_area(&self, my_aabb: AABB) -> AABB {
+        let (hw, hh) = (my_aabb.w / 2, my_aabb.h / 2);
+        match self {
+            Self::SpikesUp => AABB { h: hh, ..my_aabb },
+            Self::SpikesDown => AABB {
+                y: my_aabb.y + hh,
+                h: hh,
+                ..my_aabb
+            },
+            Self::SpikesLeft => AABB { w: hw, ..my_aabb },
+            Self::SpikesRight => AABB {
+                x: my_aabb.x + hw,
+                w: hw,
+                ..my_aabb
+            },
+            _ => my_aabb,
+        }
+    }
+
+    pub fn spike_state(&self, gs: &GlobalState) -> SpikeState {
+        match self {
+            Self::TimedSpikes(cycle, offset) => {
+                let t = (gs.timer + offset).rem_euclid(*cycle);
+                if t < cycle / 2 {
+                    SpikeState::Extended
+                } else if t >= cycle - (cycle / 4).min(20) {
+                    SpikeState::Warning
+                } else {
+                    SpikeState::Retracted
+                }
+            }
+            _ => SpikeState::Extended,
+        }
+    }
+
     pub fn from_string(s: &str) -> Self {
+        if let Some(args) = s.strip_prefix("timedspikes") {
+            let mut args = args
+                .split_whitespace()
+                .map(|a| a.parse().expect("timed spike arguments should be numbers"));
+            let cycle = args.next().unwrap_or(120).max(2);
+            let offset = args.next().unwrap_or(0);
+            return Self::TimedSpikes(cycle, offset);
+        }
+        let mut words = s.split_whitespace();
+        if let Some(p) = words.next().and_then(Pickup::from_name) {
+            let duration = words
+                .next()
+                .map(|d| d.parse().expect("pickup duration should be a number"))
+                .unwrap_or(0);
+            return Self::Pickup(p, duration);
+        }
         match s {
             "wall" => Self::Wall,
             "wall2" => Self::Wall2,
             "wall3" => Self::Wall3,
             "wall4" => Self::Wall4,
+            "breakable" => Self::Breakable,
+
+            "halfblocktop" => Self::HalfBlockTop,
+            "halfblockbottom" => Self::HalfBlockBottom,
+            "halfblockleft" => Self::HalfBlockLeft,
+            "halfblockright" => Self::HalfBlockRight,
+            "ledge" => Self::Ledge,
+            "pillar" => Self::Pillar,
 
             "backwall" => Self::BackWall,
             "backwall2" => Self::BackWall2,
@@ -326,12 +822,23 @@ impl Tile {
             "exit_anchor" => Self::ExitAnchor,
 
             "spikes" => Self::Spikes,
+            "spikesup" => Self::SpikesUp,
+            "spikesdown" => Self::SpikesDown,
+            "spikesleft" => Self::SpikesLeft,
+            "spikesright" => Self::SpikesRight,
 
             "onewayleft" => Self::OneWayLeft,
             "onewayright" => Self::OneWayRight,
             "onewaydown" => Self::OneWayDown,
             "onewayup" => Self::OneWayUp,
 
+            "slopeupright" => Self::SlopeUpRight,
+            "slopeupleft" => Self::SlopeUpLeft,
+            "gentleslopeuprightlow" => Self::GentleSlopeUpRightLow,
+            "gentleslopeuprighthigh" => Self::GentleSlopeUpRightHigh,
+            "gentleslopeupleftlow" => Self::GentleSlopeUpLeftLow,
+            "gentleslopeuplefthigh" => Self::GentleSlopeUpLeftHigh,
+
             "redkey" => Self::RedKey,
             "yellowkey" => Self::YellowKey,
             "greenkey" => Self::GreenKey,
@@ -356,15 +863,27 @@ impl Tile {
             "slowsawlauncherup" => Self::SlowSawLauncherUp,
             "slowsawlauncherdown" => Self::SlowSawLauncherDown,
 
+            "bouncysawlauncherleft" => Self::BouncySawLauncherLeft,
+            "bouncysawlauncherright" => Self::BouncySawLauncherRight,
+            "bouncysawlauncherup" => Self::BouncySawLauncherUp,
+            "bouncysawlauncherdown" => Self::BouncySawLauncherDown,
+
+            "turret" => Self::Turret,
+            "stalactite" => Self::Stalactite,
+
             "secret" => Self::Secret,
+            "challengesecret" => Self::ChallengeSecret,
+            "failgate" => Self::FailGate,
+            "coin" => Self::Coin,
+            "extralife" => Self::ExtraLife,
+            "checkpoint" => Self::Checkpoint,
             "goal" => Self::Goal,
 
             "jumparrow" => Self::JumpArrow,
 
             "binocular" => Self::Binocular,
 
-            "icecube" => Self::IceCube,
-            "playervanish" => Self::PlayerVanish,
+            "sign" => Self::SignGeneric,
 
             "switch" => Self::Switch,
             "switchred" => Self::SwitchRed,
@@ -379,6 +898,10 @@ impl Tile {
             Self::Door(_) | Self::DoorGeneric => Some("assets/door.png"),
             Self::SecretDoor(_) | Self::SecretDoorGeneric => Some("assets/secretdoor.png"),
             Self::Spikes => Some("assets/spike.png"),
+            Self::SpikesUp => Some("assets/spikeup.png"),
+            Self::SpikesDown => Some("assets/spikedown.png"),
+            Self::SpikesLeft => Some("assets/spikeleft.png"),
+            Self::SpikesRight => Some("assets/spikeright.png"),
 
             Self::RedKey => Some("assets/redkey.png"),
             Self::YellowKey => Some("assets/yellowkey.png"),
@@ -394,6 +917,8 @@ impl Tile {
             Self::BlueLock => Some("assets/bluelock.png"),
             Self::MagentaLock => Some("assets/magentalock.png"),
 
+            Self::Breakable => Some("assets/breakable.png"),
+
             Self::SawLauncherLeft => Some("assets/sawlauncherleft.png"),
             Self::SawLauncherRight => Some("assets/sawlauncherright.png"),
             Self::SawLauncherUp => Some("assets/sawlauncherup.png"),
@@ -404,7 +929,20 @@ impl Tile {
             Self::SlowSawLauncherUp => Some("assets/slowsawlauncherup.png"),
             Self::SlowSawLauncherDown => Some("assets/slowsawlauncherdown.png"),
 
+            Self::BouncySawLauncherLeft => Some("assets/bouncysawlauncherleft.png"),
+            Self::BouncySawLauncherRight => Some("assets/bouncysawlauncherright.png"),
+            Self::BouncySawLauncherUp => Some("assets/bouncysawlauncherup.png"),
+            Self::BouncySawLauncherDown => Some("assets/bouncysawlauncherdown.png"),
+
+            Self::Turret => Some("assets/turret.png"),
+            Self::Stalactite => Some("assets/stalactite.png"),
+
             Self::Secret => Some("assets/secret.png"),
+            Self::ChallengeSecret => Some("assets/challengesecret.png"),
+            Self::FailGate => Some("assets/failgate.png"),
+            Self::Coin => Some("assets/coin.png"),
+            Self::ExtraLife => Some("assets/extralife.png"),
+            Self::Checkpoint => Some("assets/checkpoint.png"),
             Self::Goal => Some("assets/goal.png"),
 
             Self::JumpArrow => Some("assets/jumparrow.png"),
@@ -412,8 +950,9 @@ impl Tile {
 
             Self::Binocular => Some("assets/binocular.png"),
 
-            Self::IceCube => Some("assets/icecube.png"),
-            Self::PlayerVanish => Some("assets/playervanish.png"),
+            Self::Sign(_) | Self::SignGeneric => Some("assets/sign.png"),
+
+            Self::Pickup(p, _) => Some(p.image()),
 
             _ => None,
         }
@@ -422,8 +961,33 @@ impl Tile {
     fn minimap_col(&self) -> Color {
         match self {
             Self::Wall | Self::Wall2 | Self::Wall3 => WHITE,
+            Self::SlopeUpRight
+            | Self::SlopeUpLeft
+            | Self::GentleSlopeUpRightLow
+            | Self::GentleSlopeUpRightHigh
+            | Self::GentleSlopeUpLeftLow
+            | Self::GentleSlopeUpLeftHigh => WHITE,
+            Self::HalfBlockTop
+            | Self::HalfBlockBottom
+            | Self::HalfBlockLeft
+            | Self::HalfBlockRight
+            | Self::Ledge
+            | Self::Pillar => WHITE,
             Self::Wall4 => BLUE,
-            Self::Spikes => color_u8!(255, 104, 104, 255),
+            Self::Breakable => color_u8!(168, 112, 62, 255),
+            Self::FailGate => color_u8!(255, 104, 104, 255),
+            Self::RedLock => color_u8!(255, 104, 103, 255),
+            Self::YellowLock => color_u8!(255, 255, 103, 255),
+            Self::GreenLock => color_u8!(104, 255, 103, 255),
+            Self::CyanLock => color_u8!(103, 255, 255, 255),
+            Self::BlueLock => color_u8!(103, 104, 255, 255),
+            Self::MagentaLock => color_u8!(255, 103, 255, 255),
+            Self::Spikes
+            | Self::TimedSpikes(..)
+            | Self::SpikesUp
+            | Self::SpikesDown
+            | Self::SpikesLeft
+            | Self::SpikesRight => color_u8!(255, 104, 104, 255),
             _ => color_u8!(0, 0, 0, 0),
         }
     }
@@ -536,6 +1100,93 @@ impl Tile {
                     }
                 }
             }
+            Self::SlopeUpRight
+            | Self::SlopeUpLeft
+            | Self::GentleSlopeUpRightLow
+            | Self::GentleSlopeUpRightHigh
+            | Self::GentleSlopeUpLeftLow
+            | Self::GentleSlopeUpLeftHigh => {
+                let te = &theme.slope;
+                if te.is_some() {
+                    let off = match self {
+                        Self::SlopeUpRight => 0,
+                        Self::SlopeUpLeft => 16,
+                        Self::GentleSlopeUpRightLow => 32,
+                        Self::GentleSlopeUpRightHigh => 48,
+                        Self::GentleSlopeUpLeftLow => 64,
+                        Self::GentleSlopeUpLeftHigh => 80,
+                        _ => unreachable!(),
+                    };
+                    let t = texture_cache!(textures, te.as_ref().expect("it exists"));
+                    draw_texture_ex(
+                        &t,
+                        x as f32,
+                        y as f32,
+                        WHITE,
+                        DrawTextureParams {
+                            source: Some(Rect {
+                                x: off as f32,
+                                y: 0.,
+                                w: 16.,
+                                h: 16.,
+                            }),
+                            ..Default::default()
+                        },
+                    )
+                } else {
+                    let (l, r) = self.slope_ends().expect("is a slope");
+                    let (l, r) = (l / PIXEL_SIZE, r / PIXEL_SIZE);
+                    let bl = vec2(x as f32, (y + TILE_PIXELS) as f32);
+                    let br = vec2((x + TILE_PIXELS) as f32, (y + TILE_PIXELS) as f32);
+                    let tl = vec2(x as f32, (y + TILE_PIXELS - l) as f32);
+                    let tr = vec2((x + TILE_PIXELS) as f32, (y + TILE_PIXELS - r) as f32);
+                    draw_triangle(bl, br, tr, BLACK);
+                    draw_triangle(bl, tr, tl, BLACK);
+                }
+            }
+            Self::HalfBlockTop
+            | Self::HalfBlockBottom
+            | Self::HalfBlockLeft
+            | Self::HalfBlockRight
+            | Self::Ledge
+            | Self::Pillar => {
+                let te = &theme.shapes;
+                if te.is_some() {
+                    let off = match self {
+                        Self::HalfBlockTop => 0,
+                        Self::HalfBlockBottom => 16,
+                        Self::HalfBlockLeft => 32,
+                        Self::HalfBlockRight => 48,
+                        Self::Ledge => 64,
+                        Self::Pillar => 80,
+                        _ => unreachable!(),
+                    };
+                    let t = texture_cache!(textures, te.as_ref().expect("it exists"));
+                    draw_texture_ex(
+                        &t,
+                        x as f32,
+                        y as f32,
+                        WHITE,
+                        DrawTextureParams {
+                            source: Some(Rect {
+                                x: off as f32,
+                                y: 0.,
+                                w: 16.,
+                                h: 16.,
+                            }),
+                            ..Default::default()
+                        },
+                    )
+                } else {
+                    let shape = self.collision_shape(AABB {
+                        x: 0,
+                        y: 0,
+                        w: TILE_PIXELS,
+                        h: TILE_PIXELS,
+                    });
+                    draw_rect_i32(x + shape.x, y + shape.y, shape.w, shape.h, BLACK)
+                }
+            }
             Self::Switch | Self::SwitchRed | Self::SwitchBlue => {
                 let te = if gs.switched {
                     match self {
@@ -555,6 +1206,29 @@ impl Tile {
                 let t = texture_cache!(textures, te);
                 tilemap_draw(&t, x, y, touching)
             }
+            Self::TimedSpikes(..) => {
+                let frame = match self.spike_state(gs) {
+                    SpikeState::Retracted => 0,
+                    SpikeState::Warning => 16,
+                    SpikeState::Extended => 32,
+                };
+                let t = texture_cache!(textures, "assets/timedspikes.png");
+                draw_texture_ex(
+                    &t,
+                    x as f32,
+                    y as f32,
+                    WHITE,
+                    DrawTextureParams {
+                        source: Some(Rect {
+                            x: frame as f32,
+                            y: 0.,
+                            w: 16.,
+                            h: 16.,
+                        }),
+                        ..Default::default()
+                    },
+                )
+            }
             _ => {
                 let t = self.sprite();
                 if t.is_some() {
@@ -657,30 +1331,155 @@ fn check_tilemap_collision(
     })
 }
 
-fn check_tilemap_wallslideable(c_box: AABB, map: &Vec<Vec<Vec<Tile>>>) -> bool {
-    !check_tilemap_condition(c_box, map, |t, _, _| t == Tile::Wall4)
+fn check_tilemap_wallslideable(c_box: AABB, map: &Vec<Vec<Vec<Tile>>>, gs: &GlobalState) -> bool {
+    !check_tilemap_condition(c_box, map, |t, _, _| t.surface(gs).nogrip)
+}
+
+fn check_tilemap_ice(c_box: AABB, map: &Vec<Vec<Vec<Tile>>>, gs: &GlobalState) -> bool {
+    check_tilemap_condition(c_box, map, |t, _, _| t.surface(gs).ice)
+}
+
+fn check_tilemap_sticky(c_box: AABB, map: &Vec<Vec<Vec<Tile>>>, gs: &GlobalState) -> bool {
+    check_tilemap_condition(c_box, map, |t, _, _| t.surface(gs).sticky)
 }
 
-pub fn check_tilemap_death(c_box: AABB, map: &Vec<Vec<Vec<Tile>>>) -> bool {
-    check_tilemap_condition(c_box, map, |t, _, _| t.is_deadly())
+fn count_coins(tiles: &[Vec<Vec<Tile>>]) -> i32 {
+    tiles
+        .iter()
+        .flatten()
+        .flatten()
+        .filter(|t| **t == Tile::Coin)
+        .count() as i32
+}
+
+pub fn check_tilemap_death(c_box: AABB, map: &Vec<Vec<Vec<Tile>>>, gs: &GlobalState) -> bool {
+    check_tilemap_condition(c_box, map, |t, ty, tx| {
+        let my_aabb = AABB {
+            x: tx as i32 * TILE_SIZE,
+            y: ty as i32 * TILE_SIZE,
+            w: TILE_SIZE,
+            h: TILE_SIZE,
+        };
+
+        t.is_deadly(gs) && t.deadly_area(my_aabb).intersect(&c_box)
+    })
 }
 
 pub fn check_tilemap_win(c_box: AABB, map: &Vec<Vec<Vec<Tile>>>) -> bool {
     check_tilemap_condition(c_box, map, |t, _, _| t == Tile::Goal)
 }
 
+// finds the slope surface nearest to a point, as an absolute y
+fn check_tilemap_slope(x: i32, y: i32, map: &[Vec<Vec<Tile>>]) -> Option<i32> {
+    if x < 0 || x >= map[0][0].len() as i32 * TILE_SIZE {
+        return None;
+    }
+    let (tx, local_x) = ((x / TILE_SIZE) as usize, x % TILE_SIZE);
+
+    let mut nearest: Option<i32> = None;
+    let base = y.div_euclid(TILE_SIZE);
+    for ty in (base - 1)..=(base + 1) {
+        if ty < 0 || ty >= map[0].len() as i32 {
+            continue;
+        }
+        for l in map {
+            if let Some(h) = l[ty as usize][tx].slope_height(local_x) {
+                let surface = (ty + 1) * TILE_SIZE - h;
+                if nearest.is_none_or(|n| (surface - y).abs() < (n - y).abs()) {
+                    nearest = Some(surface);
+                }
+            }
+        }
+    }
+
+    nearest
+}
+
+// saws, shots and turret sight treat slopes as walls, checked at the bottom middle of c_box
+fn check_tilemap_in_slope(c_box: AABB, map: &[Vec<Vec<Tile>>]) -> bool {
+    let (x, y) = (c_box.x + c_box.w / 2, c_box.y + c_box.h - 1);
+    check_tilemap_slope(x, y, map).is_some_and(|surface| y >= surface)
+}
+
+// breaks the row of tiles right under c_box, returns whether anything broke
+pub fn break_tiles_below(
+    c_box: AABB,
+    my_screen: usize,
+    map: &mut [Vec<Vec<Tile>>],
+    gs: &mut GlobalState,
+) -> bool {
+    let ty = (c_box.y + c_box.h) / TILE_SIZE;
+    if ty < 0 || ty >= map[0].len() as i32 {
+        return false;
+    }
+    let ty = ty as usize;
+
+    let mut broke = false;
+    for tx in (c_box.x / TILE_SIZE)..=((c_box.x + c_box.w - 1) / TILE_SIZE) {
+        if tx < 0 || tx >= map[0][0].len() as i32 {
+            continue;
+        }
+        let tx = tx as usize;
+        for (li, l) in map.iter_mut().enumerate() {
+            if l[ty][tx] == Tile::Breakable {
+                l[ty][tx] = Tile::Empty;
+                gs.changed_tiles
+                    .insert((my_screen, li, ty, tx), Tile::Empty);
+                broke = true;
+            }
+        }
+    }
+
+    broke
+}
+
 pub fn check_tilemap_switch(c_box: AABB, map: &Vec<Vec<Vec<Tile>>>) -> bool {
     check_tilemap_condition(c_box, map, |t, _, _| t == Tile::Switch)
 }
 
+// walks a straight line between two points, checking that nothing solid is in the way
+pub fn check_tilemap_sight(
+    from: (i32, i32),
+    to: (i32, i32),
+    map: &Vec<Vec<Vec<Tile>>>,
+    gs: &GlobalState,
+) -> bool {
+    let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+    let steps = (dx.abs().max(dy.abs()) / (TILE_SIZE / 4)).max(1);
+    for i in 0..=steps {
+        let point = AABB {
+            x: from.0 + dx * i / steps,
+            y: from.1 + dy * i / steps,
+            w: 1,
+            h: 1,
+        };
+        if check_tilemap_collision(point, point, map, Direction::Up, gs)
+            || check_tilemap_in_slope(point, map)
+        {
+            return false;
+        }
+    }
+
+    true
+}
+
 pub fn check_object_death(c_box: AABB, objects: &Vec<Box<dyn Object>>) -> bool {
     for o in objects {
         match o.get_type() {
-            "SAW" => {
+            "SAW" | "TURRETSHOT" => {
                 if o.get_aabb().smaller_by(PIXEL_SIZE * 2).intersect(&c_box) {
                     return true;
                 }
             }
+            "STALACTITE" => {
+                let stalactite = o
+                    .as_any()
+                    .downcast_ref::<Stalactite>()
+                    .expect("it is a stalactite");
+                if stalactite.shatter_timer == 0 && o.get_aabb().intersect(&c_box) {
+                    return true;
+                }
+            }
             _ => (),
         }
     }
@@ -733,6 +1532,7 @@ pub fn collect_keys(
         (0..c_box.h).step_by(TILE_SIZE as usize).collect()
     };
 
+    let mut beat_challenge = false;
     for x in xi.iter() {
         for y in yi.iter() {
             let (tx, ty) = ((c_box.x + x) / TILE_SIZE, (c_box.y + y) / TILE_SIZE);
@@ -769,17 +1569,41 @@ pub fn collect_keys(
                         gs.changed_tiles
                             .insert((my_screen, li, ty, tx), Tile::Empty);
                     }
+                    Tile::ChallengeSecret => {
+                        gs.secrets += 1;
+                        gs.challenge_timer = None;
+
+                        l[ty][tx] = Tile::Empty;
+                        gs.changed_tiles
+                            .insert((my_screen, li, ty, tx), Tile::Empty);
+                        beat_challenge = true;
+                    }
+                    Tile::Coin => {
+                        gs.coins += 1;
+
+                        l[ty][tx] = Tile::Empty;
+                        gs.changed_tiles
+                            .insert((my_screen, li, ty, tx), Tile::Empty);
+                    }
+                    Tile::ExtraLife => {
+                        if gs.default_modifiers.lives > 0 {
+                            gs.lives += 1;
+                        }
+
+                        l[ty][tx] = Tile::Empty;
+                        gs.changed_tiles
+                            .insert((my_screen, li, ty, tx), Tile::Empty);
+                    }
+                    Tile::Checkpoint => {
+                        gs.checkpoint = Some(my_screen);
+                    }
                     Tile::JumpArrow => {
                         gs.jumps += 1;
                         l[ty][tx] = Tile::JumpArrowOutline;
                         gs.collected_jump_arrows.push_back((li, ty, tx));
                     }
-                    Tile::IceCube => {
-                        gs.modifiers.superslippery = true;
-                        l[ty][tx] = Tile::Empty;
-                    }
-                    Tile::PlayerVanish => {
-                        gs.modifiers.invisibleplayer = true;
+                    Tile::Pickup(p, duration) => {
+                        gs.collect_pickup(p, duration);
                         l[ty][tx] = Tile::Empty;
                     }
 
@@ -788,7 +1612,13 @@ pub fn collect_keys(
             }
         }
     }
-
+    // beat the clock, so let the player back out
+    if beat_challenge {
+        for (li, y, x) in open_fail_gates(map) {
+            gs.changed_tiles.insert((my_screen, li, y, x), Tile::Empty);
+        }
+    }
+
     false
 }
 pub fn collect_doors(
@@ -888,6 +1718,9 @@ pub fn check_door(c_box: AABB, map: &Vec<Vec<Vec<Tile>>>) -> Option<Tile> {
                 if l[ty][tx] == Tile::Binocular {
                     return Some(l[ty][tx]);
                 }
+                if let Tile::Sign(_) = l[ty][tx] {
+                    return Some(l[ty][tx]);
+                }
             }
         }
     }
@@ -916,6 +1749,36 @@ pub fn find_door(index: usize, map: &Vec<Vec<Vec<Tile>>>) -> Option<(i32, i32)>
     None
 }
 
+// returns where the gates were, as (layer, y, x)
+fn open_fail_gates(map: &mut [Vec<Vec<Tile>>]) -> Vec<(usize, usize, usize)> {
+    let mut opened = vec![];
+    for (li, layer) in map.iter_mut().enumerate() {
+        for (y, row) in layer.iter_mut().enumerate() {
+            for (x, tile) in row.iter_mut().enumerate() {
+                if *tile == Tile::FailGate {
+                    *tile = Tile::Empty;
+                    opened.push((li, y, x));
+                }
+            }
+        }
+    }
+    opened
+}
+
+pub fn find_checkpoint(map: &[Vec<Vec<Tile>>]) -> Option<(i32, i32)> {
+    for layer in map.iter() {
+        for (y, row) in layer.iter().enumerate() {
+            for (x, tile) in row.iter().enumerate() {
+                if *tile == Tile::Checkpoint {
+                    return Some((x as i32, y as i32));
+                }
+            }
+        }
+    }
+
+    None
+}
+
 pub trait Object {
     fn get_type(&self) -> &'static str;
 
@@ -985,6 +1848,91 @@ pub struct Player {
     pub wall_sliding: i32,
 
     pub air_frames: i32,
+    pub jump_buffer: i32,
+    // ticks left to wall jump after letting go of a wall, and which wall it was
+    pub wall_grace: i32,
+    pub last_wall: i32,
+    pub sticky_wall: bool,
+
+    pub on_slope: bool,
+
+    pub pounding: bool,
+    // set on the frame a ground pound hits the floor, cleared by whoever breaks the blocks
+    pub pound_landed: bool,
+    pub shockwave: i32,
+}
+
+impl Player {
+    // on a slope the corners of our box sink into the ground the slope runs into.
+    // sideways, anything we'd clear by stepping up half a tile is that ground and not a wall,
+    // and going down only the middle, where our feet are, can land
+    fn collides(
+        &self,
+        before_aabb: AABB,
+        c_box: AABB,
+        direction: Direction,
+        tiles: &Vec<Vec<Vec<Tile>>>,
+        gs: &GlobalState,
+    ) -> bool {
+        if !check_tilemap_collision(before_aabb, c_box, tiles, direction, gs) {
+            return false;
+        }
+        if !self.on_slope {
+            return true;
+        }
+        match direction {
+            Direction::Left | Direction::Right => (1..=TILE_SIZE / 2 / PIXEL_SIZE).all(|i| {
+                check_tilemap_collision(
+                    before_aabb,
+                    c_box.shift_by((0, -i * PIXEL_SIZE)),
+                    tiles,
+                    direction,
+                    gs,
+                )
+            }),
+            Direction::Down => {
+                let feet = AABB {
+                    x: c_box.x + TILE_SIZE / 2 - PIXEL_SIZE / 2,
+                    y: c_box.y,
+                    w: PIXEL_SIZE,
+                    h: c_box.h,
+                };
+                check_tilemap_collision(before_aabb, feet, tiles, direction, gs)
+            }
+            Direction::Up => true,
+        }
+    }
+
+    // step a pixel at a time towards what we bumped into,
+    // so we end up flush against shapes that don't fill their tile
+    fn creep(
+        &mut self,
+        before_aabb: AABB,
+        step: (i32, i32),
+        max: i32,
+        tiles: &Vec<Vec<Vec<Tile>>>,
+        gs: &GlobalState,
+    ) {
+        let direction = if step.0 != 0 {
+            Direction::h_vel(step.0)
+        } else {
+            Direction::v_vel(step.1)
+        };
+        let mut moved = PIXEL_SIZE;
+        while moved <= max
+            && !self.collides(
+                before_aabb,
+                self.get_aabb().shift_by(step),
+                direction,
+                tiles,
+                gs,
+            )
+        {
+            self.x += step.0;
+            self.y += step.1;
+            moved += PIXEL_SIZE;
+        }
+    }
 }
 
 impl Object for Player {
@@ -1011,21 +1959,34 @@ impl Object for Player {
         if global_state.binocularing {
             return;
         }
+        if self.shockwave > 0 {
+            self.shockwave += 1;
+            if self.shockwave > 16 {
+                self.shockwave = 0;
+            }
+        }
+        let phys = global_state.physics;
+        // remember jump presses for a bit so ones just before landing still count
+        if *keys_pressed.entry(KeyCode::Z).or_insert(false) {
+            self.jump_buffer = phys.jump_buffer_frames;
+        } else {
+            self.jump_buffer = (self.jump_buffer - 1).max(0);
+        }
         // accelerate left and right
         self.freeze_timer -= 1;
-        let unslippy = check_tilemap_wallslideable(self.get_aabb().shift_by((0, 4)), &tiles);
+        let unslippy = !check_tilemap_ice(self.get_aabb().shift_by((0, 4)), tiles, global_state);
         if self.freeze_timer <= 0 {
             if is_key_down(KeyCode::Left) && !is_key_down(KeyCode::Right) {
                 if self.wall_sliding > 0 {
                     self.wall_sliding = 0
                 }
-                if self.vx >= -MAX_PLAYER_SPEED {
+                if self.vx >= -phys.max_speed {
                     if global_state.modifiers.superslippery {
-                        self.vx = (-MAX_PLAYER_SPEED).max(self.vx - PLAYER_ACCEL / 8);
+                        self.vx = (-phys.max_speed).max(self.vx - phys.accel / 8);
                     } else if unslippy {
-                        self.vx = (-MAX_PLAYER_SPEED).max(self.vx - PLAYER_ACCEL);
+                        self.vx = (-phys.max_speed).max(self.vx - phys.accel);
                     } else {
-                        self.vx = (-MAX_PLAYER_SPEED).max(self.vx - PLAYER_ACCEL / 2);
+                        self.vx = (-phys.max_speed).max(self.vx - phys.accel / 2);
                     }
                 } else {
                     self.vx += TILE_SIZE / 128;
@@ -1034,13 +1995,13 @@ impl Object for Player {
                 if self.wall_sliding > 0 {
                     self.wall_sliding = 0
                 }
-                if self.vx <= MAX_PLAYER_SPEED {
+                if self.vx <= phys.max_speed {
                     if global_state.modifiers.superslippery {
-                        self.vx = (MAX_PLAYER_SPEED).min(self.vx + PLAYER_ACCEL / 8);
+                        self.vx = (phys.max_speed).min(self.vx + phys.accel / 8);
                     } else if unslippy {
-                        self.vx = (MAX_PLAYER_SPEED).min(self.vx + PLAYER_ACCEL);
+                        self.vx = (phys.max_speed).min(self.vx + phys.accel);
                     } else {
-                        self.vx = (MAX_PLAYER_SPEED).min(self.vx + PLAYER_ACCEL / 2);
+                        self.vx = (phys.max_speed).min(self.vx + phys.accel / 2);
                     }
                 } else {
                     self.vx -= TILE_SIZE / 128;
@@ -1062,16 +2023,32 @@ impl Object for Player {
                 self.vx = self.wall_sliding;
             }
         } else {
-            self.anim_timer += self.vx.abs() / PLAYER_ACCEL;
+            self.anim_timer += self.vx.abs() / phys.accel;
         }
 
         if is_key_down(KeyCode::Down) {
-            self.vy += TILE_SIZE / 16;
+            self.vy += phys.fast_gravity;
         } else if is_key_down(KeyCode::Z) {
-            self.vy += TILE_SIZE / 16 / 5;
+            self.vy += phys.jump_gravity;
         } else {
             self.freeze_timer -= 5;
-            self.vy += TILE_SIZE / 16 / 2;
+            self.vy += phys.gravity;
+        }
+
+        // ground pound, takes priority over midair jumps
+        if !self.grounded
+            && !self.pounding
+            && self.wall_sliding == 0
+            && is_key_down(KeyCode::Down)
+            && self.jump_buffer > 0
+        {
+            self.jump_buffer = 0;
+            self.pounding = true;
+            self.vy = -TILE_SIZE / 8;
+        }
+        if self.pounding {
+            self.vx = 0;
+            self.vy = (self.vy + TILE_SIZE / 8).min(TILE_SIZE * 3 / 4);
         }
         // cap vx and vy at one tile per game step
         // in practice this will never be hit
@@ -1086,6 +2063,10 @@ impl Object for Player {
         // horizontal movement
         // move to tile boundary if we are moving too fast
 
+        let foot = (self.x + TILE_SIZE / 2, self.y + TILE_SIZE);
+        self.on_slope = check_tilemap_slope(foot.0, foot.1, tiles)
+            .is_some_and(|surface| (surface - foot.1).abs() <= TILE_SIZE / 2);
+
         let before_aabb = self.get_aabb();
 
         let remaining_movement = if (self.x + self.vx) / TILE_SIZE != self.x / TILE_SIZE {
@@ -1111,12 +2092,12 @@ impl Object for Player {
                     break;
                 }
 
-                if check_tilemap_collision(
+                if self.collides(
                     before_aabb,
                     self.get_aabb(),
-                    tiles,
                     Direction::h_vel(self.vx),
-                    &global_state,
+                    tiles,
+                    global_state,
                 ) {
                     self.x -= TILE_SIZE * self.vx.signum();
                     // continue;
@@ -1126,16 +2107,24 @@ impl Object for Player {
         } // now we are aligned at tile boundary, do remaining movement,
           // then step back if we are then colliding
         self.x += temp_movement;
-        if check_tilemap_collision(
+        if self.collides(
             before_aabb,
             self.get_aabb(),
-            tiles,
             Direction::h_vel(self.vx),
-            &global_state,
+            tiles,
+            global_state,
         ) {
-            let can_wallslide = check_tilemap_wallslideable(self.get_aabb(), tiles)
-                && !global_state.modifiers.nowalljump;
+            let can_wallslide = check_tilemap_wallslideable(self.get_aabb(), tiles, global_state)
+                && !global_state.modifiers.nowalljump
+                && !self.on_slope;
             self.x -= remaining_movement;
+            self.creep(
+                before_aabb,
+                (PIXEL_SIZE * self.vx.signum(), 0),
+                remaining_movement.abs(),
+                tiles,
+                global_state,
+            );
             self.freeze_timer = 0;
             if ((self.vx < 0 && is_key_down(KeyCode::Left))
                 || (self.vx > 0 && is_key_down(KeyCode::Right)))
@@ -1157,43 +2146,69 @@ impl Object for Player {
             self.wall_sliding = 0;
         }
 
+        if self.wall_sliding != 0 {
+            self.last_wall = self.wall_sliding;
+            self.wall_grace = phys.wall_coyote_frames;
+            self.sticky_wall = check_tilemap_sticky(
+                self.get_aabb()
+                    .shift_by((self.wall_sliding * PIXEL_SIZE, 0)),
+                tiles,
+                global_state,
+            );
+        } else {
+            self.wall_grace = (self.wall_grace - 1).max(0);
+        }
+
         if self.wall_sliding != 0 {
             self.vx = -self.wall_sliding;
             if is_key_down(KeyCode::Down) {
                 self.vy = self.vy.min(TILE_SIZE / 4);
+            } else if self.sticky_wall {
+                self.vy = self.vy.min(TILE_SIZE / 128);
             } else {
                 self.vy = self.vy.min(TILE_SIZE / 32);
             }
-            if *keys_pressed.entry(KeyCode::Z).or_insert(false) && self.air_frames != 0 {
-                self.grounded = false;
-                self.freeze_timer = 14;
-                if self.wall_sliding < 0 {
-                    self.vx = TILE_SIZE * 5 / 16;
-                    self.vy = -TILE_SIZE * 4 / 16;
-                } else if self.wall_sliding > 0 {
-                    self.vx = -TILE_SIZE * 5 / 16;
-                    self.vy = -TILE_SIZE * 4 / 16;
-                }
-                self.wall_sliding = 0;
-                let s = sound_cache!(sounds, "assets/mus/jump.ogg");
-                play_sound(
-                    &s,
-                    PlaySoundParams {
-                        looped: false,
-                        volume: 0.2,
-                    },
-                );
+        }
+        if (self.wall_sliding != 0 || (self.wall_grace > 0 && !self.grounded))
+            && self.jump_buffer > 0
+            && self.air_frames != 0
+        {
+            self.grounded = false;
+            self.freeze_timer = phys.wall_jump_freeze;
+            let wall_jump = if self.sticky_wall {
+                (phys.wall_jump_vx * 5 / 4, phys.wall_jump_vy * 5 / 4)
+            } else {
+                (phys.wall_jump_vx, phys.wall_jump_vy)
+            };
+            if self.last_wall < 0 {
+                self.vx = wall_jump.0;
+                self.vy = -wall_jump.1;
+            } else if self.last_wall > 0 {
+                self.vx = -wall_jump.0;
+                self.vy = -wall_jump.1;
             }
+            self.wall_sliding = 0;
+            self.wall_grace = 0;
+            self.jump_buffer = 0;
+            let s = sound_cache!(sounds, "assets/mus/jump.ogg");
+            play_sound(
+                &s,
+                PlaySoundParams {
+                    looped: false,
+                    volume: 0.2,
+                },
+            );
         }
 
         if (self.grounded
             || (global_state.jumps > 0 && self.freeze_timer <= 0)
             || global_state.modifiers.infinitejumps)
-            && *keys_pressed.entry(KeyCode::Z).or_insert(false)
+            && self.jump_buffer > 0
         {
-            self.vy = -TILE_SIZE * 5 / 16;
+            self.jump_buffer = 0;
+            self.vy = -phys.jump_velocity;
             if is_key_down(KeyCode::Up) {
-                self.vy = -(self.vx.abs().max(TILE_SIZE * 5 / 16));
+                self.vy = -(self.vx.abs().max(phys.jump_velocity));
                 self.vx /= 8;
             }
             if is_key_down(KeyCode::Left) && is_key_down(KeyCode::Right) {
@@ -1224,9 +2239,9 @@ impl Object for Player {
             self.air_frames = self.air_frames.max(1)
         }
         if is_key_down(KeyCode::Down) {
-            self.air_frames += 15
+            self.air_frames += phys.coyote_frames
         }
-        if self.air_frames > 15 {
+        if self.air_frames > phys.coyote_frames {
             self.grounded = false
         }
 
@@ -1245,21 +2260,33 @@ impl Object for Player {
         };
 
         self.y += remaining_movement;
-        if check_tilemap_collision(
+        if self.collides(
             before_aabb,
             self.get_aabb(),
-            tiles,
             Direction::v_vel(self.vy),
-            &global_state,
+            tiles,
+            global_state,
         ) {
-            if self.vy < -PIXEL_SIZE {
+            if self.vy < -PIXEL_SIZE || self.pounding {
                 if check_tilemap_switch(self.get_aabb(), tiles) {
                     global_state.switched = !global_state.switched;
                 }
             }
             self.y -= remaining_movement;
+            self.creep(
+                before_aabb,
+                (0, PIXEL_SIZE * self.vy.signum()),
+                remaining_movement.abs(),
+                tiles,
+                global_state,
+            );
             if self.vy > 0 {
                 // going down, we have just landed
+                if self.pounding {
+                    self.pounding = false;
+                    self.pound_landed = true;
+                    self.shockwave = 1;
+                }
                 self.grounded = true;
                 self.vy = 0;
                 self.air_frames = 0;
@@ -1269,6 +2296,29 @@ impl Object for Player {
             self.wall_sliding = 0;
         }
 
+        // put our feet on any slope we sank into, or keep them on it while running downhill
+        if self.vy >= 0 {
+            let foot = (self.x + TILE_SIZE / 2, self.y + TILE_SIZE);
+            if let Some(surface) = check_tilemap_slope(foot.0, foot.1, tiles) {
+                let sunk = foot.1 - surface;
+                if (0..=TILE_SIZE).contains(&sunk)
+                    || (self.grounded && -sunk <= self.vx.abs() + TILE_SIZE / 4)
+                {
+                    self.y = surface - TILE_SIZE;
+                    if self.pounding {
+                        self.pounding = false;
+                        self.pound_landed = true;
+                        self.shockwave = 1;
+                    }
+                    self.grounded = true;
+                    self.on_slope = true;
+                    self.vy = 0;
+                    self.air_frames = 0;
+                    self.wall_sliding = 0;
+                }
+            }
+        }
+
         // do it again because this was bugging me
         if !global_state.modifiers.uncapped_speed {
             self.vx = self.vx.clamp(-TILE_SIZE, TILE_SIZE);
@@ -1292,6 +2342,32 @@ impl Object for Player {
         //     BLUE,
         // );
 
+        if self.shockwave > 0 {
+            let sw = texture_cache!(textures, "assets/shockwave.png");
+            let spread = self.shockwave / 2;
+            for (x, flip) in [
+                (self.x / PIXEL_SIZE + off_x - 12 - spread, true),
+                (self.x / PIXEL_SIZE + off_x + 12 + spread, false),
+            ] {
+                draw_texture_ex(
+                    &sw,
+                    x as f32,
+                    (self.y / PIXEL_SIZE + off_y + 8) as f32,
+                    WHITE,
+                    DrawTextureParams {
+                        source: Some(Rect {
+                            x: ((self.shockwave - 1) / 4 * 16) as f32,
+                            y: 0.,
+                            w: 16.,
+                            h: 8.,
+                        }),
+                        flip_x: flip,
+                        ..Default::default()
+                    },
+                );
+            }
+        }
+
         let t = texture_cache!(textures, "assets/player.png");
 
         let mut draw_offset = (0, 0);
@@ -1464,6 +2540,9 @@ pub struct Saw {
 
     pub anim_timer: i32,
 
+    // how many more walls this saw can bounce off before breaking
+    pub bounces: i32,
+    pub bouncy: bool,
     pub should_remove: bool,
 }
 
@@ -1516,8 +2595,15 @@ impl Object for Saw {
             tiles,
             Direction::h_vel(self.vx),
             &global_state,
-        ) {
-            self.should_remove = true
+        ) || check_tilemap_in_slope(self.get_aabb(), tiles)
+        {
+            if self.bounces > 0 {
+                self.bounces -= 1;
+                self.x = before_aabb.x;
+                self.vx *= -1;
+            } else {
+                self.should_remove = true
+            }
         }
 
         let before_aabb = self.get_aabb();
@@ -1541,8 +2627,15 @@ impl Object for Saw {
             tiles,
             Direction::v_vel(self.vy),
             &global_state,
-        ) {
-            self.should_remove = true
+        ) || check_tilemap_in_slope(self.get_aabb(), tiles)
+        {
+            if self.bounces > 0 {
+                self.bounces -= 1;
+                self.y = before_aabb.y;
+                self.vy *= -1;
+            } else {
+                self.should_remove = true
+            }
         }
     }
 
@@ -1562,7 +2655,11 @@ impl Object for Saw {
         //     BLUE,
         // );
 
-        let t = texture_cache!(textures, "assets/saw.png");
+        let t = if self.bouncy {
+            texture_cache!(textures, "assets/bouncysaw.png")
+        } else {
+            texture_cache!(textures, "assets/saw.png")
+        };
 
         let mut draw_offset = (0, 0);
         if self.anim_timer % 16 >= 8 {
@@ -1607,6 +2704,7 @@ pub struct SawLauncher {
     pub vy: i32,
 
     pub frames: i32,
+    pub bounces: i32,
 }
 
 impl Object for SawLauncher {
@@ -1661,23 +2759,33 @@ impl Object for SawLauncher {
             vx: self.vx,
             vy: self.vy,
             anim_timer: 0,
+            bounces: self.bounces,
+            bouncy: self.bounces > 0,
             should_remove: false,
         }))
     }
 }
-pub struct ArrowRespawn {
+
+pub struct Turret {
     pub x: i32,
     pub y: i32,
 
-    pub frames: i32,
-    pub layer: usize,
-    pub xi: usize,
-    pub yi: usize,
+    pub cooldown: i32,
+    pub windup: i32,
+
+    // velocity of the shot fired this frame, if any
+    pub shot: Option<(i32, i32)>,
 }
 
-impl Object for ArrowRespawn {
+impl Turret {
+    fn center(&self) -> (i32, i32) {
+        (self.x + TILE_SIZE / 2, self.y + TILE_SIZE / 2)
+    }
+}
+
+impl Object for Turret {
     fn get_type(&self) -> &'static str {
-        "ARROWRESPAWN"
+        "TURRET"
     }
 
     fn get_aabb(&self) -> AABB {
@@ -1693,20 +2801,30 @@ impl Object for ArrowRespawn {
         &mut self,
         _keys_pressed: &mut HashMap<KeyCode, bool>,
         tiles: &mut Vec<Vec<Vec<Tile>>>,
-        gs: &mut GlobalState,
+        global_state: &mut GlobalState,
         _sounds: &mut HashMap<String, Sound>,
     ) {
-        if tiles[self.layer][self.yi][self.xi] != Tile::JumpArrow
-            && !gs
-                .collected_jump_arrows
-                .contains(&(self.layer, self.yi, self.xi))
-        {
-            self.frames += 1;
-            if self.frames >= 180 {
-                tiles[self.layer][self.yi][self.xi] = Tile::JumpArrow
+        self.shot = None;
+        let can_see = check_tilemap_sight(self.center(), global_state.focus, tiles, global_state);
+
+        if self.windup > 0 {
+            self.windup -= 1;
+            if self.windup == 0 {
+                if can_see {
+                    let (dx, dy) = (
+                        (global_state.focus.0 - self.center().0) as f32,
+                        (global_state.focus.1 - self.center().1) as f32,
+                    );
+                    let len = (dx * dx + dy * dy).sqrt().max(1.);
+                    let speed = (TILE_SIZE * 3 / 16) as f32;
+                    self.shot = Some(((dx / len * speed) as i32, (dy / len * speed) as i32));
+                }
+                self.cooldown = 90;
             }
-        } else {
-            self.frames = 0;
+        } else if self.cooldown > 0 {
+            self.cooldown -= 1;
+        } else if can_see {
+            self.windup = 40;
         }
     }
 
@@ -1715,47 +2833,29 @@ impl Object for ArrowRespawn {
         off_x: i32,
         off_y: i32,
         textures: &mut HashMap<String, Texture2D>,
-        gs: &GlobalState,
+        _gs: &GlobalState,
         _t: &TransitionAnimationType,
     ) {
-        let t = texture_cache!(textures, "assets/jumparrowfill.png");
-
-        let k = if gs
-            .collected_jump_arrows
-            .contains(&(self.layer, self.yi, self.xi))
-        {
-            0
+        if self.windup == 0 {
+            return;
+        }
+        // blink faster the closer we are to firing
+        let blink = if self.windup > 20 {
+            self.windup / 6 % 2 == 0
+        } else if self.windup > 8 {
+            self.windup / 3 % 2 == 0
         } else {
-            255
+            true
         };
-
-        draw_texture_ex(
-            &t,
-            (self.x / PIXEL_SIZE + off_x) as f32,
-            (self.y / PIXEL_SIZE + off_y) as f32,
-            color_u8!(255, k, k, 63),
-            DrawTextureParams {
-                ..Default::default()
-            },
-        );
-
-        let size = self.frames / 15;
-
-        draw_texture_ex(
-            &t,
-            (self.x / PIXEL_SIZE + off_x) as f32,
-            (self.y / PIXEL_SIZE + off_y + 14 - size) as f32,
-            WHITE,
-            DrawTextureParams {
-                source: Some(Rect {
-                    x: 0.,
-                    y: 14. - size as f32,
-                    w: 16.,
-                    h: size as f32,
-                }),
-                ..Default::default()
-            },
-        )
+        if blink {
+            let t = texture_cache!(textures, "assets/turretcharge.png");
+            draw_texture(
+                &t,
+                (self.x / PIXEL_SIZE + off_x) as f32,
+                (self.y / PIXEL_SIZE + off_y) as f32,
+                WHITE,
+            );
+        }
     }
 
     fn as_any(&self) -> &dyn std::any::Any {
@@ -1765,80 +2865,512 @@ impl Object for ArrowRespawn {
     fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
         self
     }
-}
 
-#[derive(Clone)]
-pub struct SideExits {
-    pub left: Option<usize>,
-    pub right: Option<usize>,
-    pub up: Option<usize>,
-    pub down: Option<usize>,
-}
+    fn spawn(&self, _gs: &GlobalState) -> Option<Box<dyn Object>> {
+        let (vx, vy) = self.shot?;
 
-#[derive(Clone)]
-pub struct SideOffsets {
-    pub left: Option<i32>,
-    pub right: Option<i32>,
-    pub up: Option<i32>,
-    pub down: Option<i32>,
+        Some(Box::new(TurretShot {
+            x: self.x + TILE_SIZE / 4,
+            y: self.y + TILE_SIZE / 4,
+            vx,
+            vy,
+            anim_timer: 0,
+            should_remove: false,
+        }))
+    }
 }
 
-#[derive(Clone)]
-pub struct LevelRaw {
-    name: String,
-    pub tiles: Vec<Vec<Vec<Tile>>>,
-    exits: SideExits,
-    door_exits: Vec<usize>,
-    theme: Option<usize>,
-}
+pub struct TurretShot {
+    pub x: i32,
+    pub y: i32,
 
-impl LevelRaw {
-    pub fn side_offsets(&self) -> SideOffsets {
-        let mut side_offsets = SideOffsets {
-            left: None,
-            right: None,
-            up: None,
-            down: None,
-        };
+    pub vx: i32,
+    pub vy: i32,
 
-        for layer in self.tiles.iter() {
-            for (y, row) in layer.iter().enumerate() {
-                for (x, tile) in row.iter().enumerate() {
-                    match tile {
-                        Tile::ExitAnchor => {
-                            if x == 0 {
-                                side_offsets.left = Some(y as i32 * TILE_SIZE)
-                            } else if x == row.len() - 1 {
-                                side_offsets.right = Some(y as i32 * TILE_SIZE)
-                            } else if y == 0 {
-                                side_offsets.up = Some(x as i32 * TILE_SIZE)
-                            } else if y == layer.len() - 1 {
-                                side_offsets.down = Some(x as i32 * TILE_SIZE)
-                            }
-                        }
-                        _ => (),
-                    }
-                }
-            }
-        }
-        side_offsets
+    pub anim_timer: i32,
+
+    pub should_remove: bool,
+}
+
+impl Object for TurretShot {
+    fn get_type(&self) -> &'static str {
+        "TURRETSHOT"
     }
-    pub fn secret_count(&self) -> i32 {
-        let mut count = 0;
 
-        for l in self.tiles.iter() {
-            for row in l {
-                for col in row {
-                    if *col == Tile::Secret {
-                        count += 1;
-                    }
-                }
-            }
+    fn get_aabb(&self) -> AABB {
+        AABB {
+            x: self.x,
+            y: self.y,
+            w: TILE_SIZE / 2,
+            h: TILE_SIZE / 2,
         }
-
-        count
     }
-    pub fn draw(
+
+    fn update(
+        &mut self,
+        _keys_pressed: &mut HashMap<KeyCode, bool>,
+        tiles: &mut Vec<Vec<Vec<Tile>>>,
+        global_state: &mut GlobalState,
+        _sounds: &mut HashMap<String, Sound>,
+    ) {
+        self.anim_timer += 1;
+
+        // shots are always slower than a tile per frame, so no need to step
+        let before_aabb = self.get_aabb();
+        self.x += self.vx;
+        if check_tilemap_collision(
+            before_aabb,
+            self.get_aabb(),
+            tiles,
+            Direction::h_vel(self.vx),
+            global_state,
+        ) || check_tilemap_in_slope(self.get_aabb(), tiles)
+        {
+            self.should_remove = true
+        }
+
+        let before_aabb = self.get_aabb();
+        self.y += self.vy;
+        if check_tilemap_collision(
+            before_aabb,
+            self.get_aabb(),
+            tiles,
+            Direction::v_vel(self.vy),
+            global_state,
+        ) || check_tilemap_in_slope(self.get_aabb(), tiles)
+        {
+            self.should_remove = true
+        }
+
+        let (w, h) = (
+            tiles[0][0].len() as i32 * TILE_SIZE,
+            tiles[0].len() as i32 * TILE_SIZE,
+        );
+        if self.x < -TILE_SIZE || self.y < -TILE_SIZE || self.x > w || self.y > h {
+            self.should_remove = true
+        }
+    }
+
+    fn draw(
+        &self,
+        off_x: i32,
+        off_y: i32,
+        textures: &mut HashMap<String, Texture2D>,
+        _gs: &GlobalState,
+        _t: &TransitionAnimationType,
+    ) {
+        let t = texture_cache!(textures, "assets/turretshot.png");
+
+        let mut draw_offset = (0, 0);
+        if self.anim_timer % 8 >= 4 {
+            draw_offset = (8, 0)
+        }
+
+        draw_texture_ex(
+            &t,
+            (self.x / PIXEL_SIZE + off_x) as f32,
+            (self.y / PIXEL_SIZE + off_y) as f32,
+            WHITE,
+            DrawTextureParams {
+                source: Some(Rect {
+                    x: draw_offset.0 as f32,
+                    y: draw_offset.1 as f32,
+                    w: 8.,
+                    h: 8.,
+                }),
+                ..Default::default()
+            },
+        )
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+
+    fn should_clear(&self) -> bool {
+        self.should_remove
+    }
+}
+pub struct Stalactite {
+    pub x: i32,
+    pub y: i32,
+
+    pub vy: i32,
+
+    pub shake_timer: i32,
+    pub falling: bool,
+    pub shatter_timer: i32,
+}
+
+impl Object for Stalactite {
+    fn get_type(&self) -> &'static str {
+        "STALACTITE"
+    }
+
+    fn get_aabb(&self) -> AABB {
+        AABB {
+            x: self.x + TILE_SIZE / 4,
+            y: self.y,
+            w: TILE_SIZE / 2,
+            h: TILE_SIZE,
+        }
+    }
+
+    fn update(
+        &mut self,
+        _keys_pressed: &mut HashMap<KeyCode, bool>,
+        tiles: &mut Vec<Vec<Vec<Tile>>>,
+        global_state: &mut GlobalState,
+        _sounds: &mut HashMap<String, Sound>,
+    ) {
+        if self.shatter_timer > 0 {
+            self.shatter_timer += 1;
+            return;
+        }
+
+        if self.falling {
+            self.vy = (self.vy + TILE_SIZE / 16 / 2).min(TILE_SIZE / 2);
+
+            let before_aabb = self.get_aabb();
+            self.y += self.vy;
+            if check_tilemap_collision(
+                before_aabb,
+                self.get_aabb(),
+                tiles,
+                Direction::Down,
+                global_state,
+            ) {
+                // land flush with the top of whatever we hit
+                self.y = (self.y / TILE_SIZE) * TILE_SIZE;
+                self.shatter_timer = 1;
+            } else if self.y > tiles[0].len() as i32 * TILE_SIZE {
+                // fell out of the room, nothing to shatter on
+                self.shatter_timer = 17;
+            }
+        } else if self.shake_timer > 0 {
+            self.shake_timer -= 1;
+            if self.shake_timer == 0 {
+                self.falling = true;
+            }
+        } else {
+            let (px, py) = global_state.focus;
+            if (px - (self.x + TILE_SIZE / 2)).abs() < TILE_SIZE * 3 / 2 && py > self.y {
+                self.shake_timer = 24;
+            }
+        }
+    }
+
+    fn draw(
+        &self,
+        off_x: i32,
+        off_y: i32,
+        textures: &mut HashMap<String, Texture2D>,
+        gs: &GlobalState,
+        _t: &TransitionAnimationType,
+    ) {
+        if self.shatter_timer > 0 {
+            let t = texture_cache!(textures, gs.stalactite_sprites.1.as_str());
+            let frame = if self.shatter_timer < 8 { 0 } else { 16 };
+            draw_texture_ex(
+                &t,
+                (self.x / PIXEL_SIZE + off_x) as f32,
+                (self.y / PIXEL_SIZE + off_y) as f32,
+                WHITE,
+                DrawTextureParams {
+                    source: Some(Rect {
+                        x: frame as f32,
+                        y: 0.,
+                        w: 16.,
+                        h: 16.,
+                    }),
+                    ..Default::default()
+                },
+            );
+            return;
+        }
+
+        let shake = if self.shake_timer > 0 {
+            self.shake_timer / 2 % 3 - 1
+        } else {
+            0
+        };
+        let t = texture_cache!(textures, gs.stalactite_sprites.0.as_str());
+        draw_texture(
+            &t,
+            (self.x / PIXEL_SIZE + off_x + shake) as f32,
+            (self.y / PIXEL_SIZE + off_y) as f32,
+            WHITE,
+        );
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+
+    fn should_clear(&self) -> bool {
+        self.shatter_timer > 16
+    }
+}
+
+pub struct ArrowRespawn {
+    pub x: i32,
+    pub y: i32,
+
+    pub frames: i32,
+    pub layer: usize,
+    pub xi: usize,
+    pub yi: usize,
+}
+
+impl Object for ArrowRespawn {
+    fn get_type(&self) -> &'static str {
+        "ARROWRESPAWN"
+    }
+
+    fn get_aabb(&self) -> AABB {
+        AABB {
+            x: self.x,
+            y: self.y,
+            w: TILE_SIZE,
+            h: TILE_SIZE,
+        }
+    }
+
+    fn update(
+        &mut self,
+        _keys_pressed: &mut HashMap<KeyCode, bool>,
+        tiles: &mut Vec<Vec<Vec<Tile>>>,
+        gs: &mut GlobalState,
+        _sounds: &mut HashMap<String, Sound>,
+    ) {
+        if tiles[self.layer][self.yi][self.xi] != Tile::JumpArrow
+            && !gs
+                .collected_jump_arrows
+                .contains(&(self.layer, self.yi, self.xi))
+        {
+            self.frames += 1;
+            if self.frames >= 180 {
+                tiles[self.layer][self.yi][self.xi] = Tile::JumpArrow
+            }
+        } else {
+            self.frames = 0;
+        }
+    }
+
+    fn draw(
+        &self,
+        off_x: i32,
+        off_y: i32,
+        textures: &mut HashMap<String, Texture2D>,
+        gs: &GlobalState,
+        _t: &TransitionAnimationType,
+    ) {
+        let t = texture_cache!(textures, "assets/jumparrowfill.png");
+
+        let k = if gs
+            .collected_jump_arrows
+            .contains(&(self.layer, self.yi, self.xi))
+        {
+            0
+        } else {
+            255
+        };
+
+        draw_texture_ex(
+            &t,
+            (self.x / PIXEL_SIZE + off_x) as f32,
+            (self.y / PIXEL_SIZE + off_y) as f32,
+            color_u8!(255, k, k, 63),
+            DrawTextureParams {
+                ..Default::default()
+            },
+        );
+
+        let size = self.frames / 15;
+
+        draw_texture_ex(
+            &t,
+            (self.x / PIXEL_SIZE + off_x) as f32,
+            (self.y / PIXEL_SIZE + off_y + 14 - size) as f32,
+            WHITE,
+            DrawTextureParams {
+                source: Some(Rect {
+                    x: 0.,
+                    y: 14. - size as f32,
+                    w: 16.,
+                    h: size as f32,
+                }),
+                ..Default::default()
+            },
+        )
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+}
+
+// rooms each side leads to, paired up in order with the exit anchors on that side
+#[derive(Clone, Default, PartialEq)]
+pub struct SideExits {
+    pub left: Vec<usize>,
+    pub right: Vec<usize>,
+    pub up: Vec<usize>,
+    pub down: Vec<usize>,
+}
+
+impl SideExits {
+    pub fn get(&self, d: Direction) -> &[usize] {
+        match d {
+            Direction::Left => &self.left,
+            Direction::Right => &self.right,
+            Direction::Up => &self.up,
+            Direction::Down => &self.down,
+        }
+    }
+}
+
+// exit anchors along each side, top to bottom or left to right
+#[derive(Clone, Default)]
+pub struct SideOffsets {
+    pub left: Vec<i32>,
+    pub right: Vec<i32>,
+    pub up: Vec<i32>,
+    pub down: Vec<i32>,
+}
+
+impl SideOffsets {
+    pub fn get(&self, d: Direction) -> &[i32] {
+        match d {
+            Direction::Left => &self.left,
+            Direction::Right => &self.right,
+            Direction::Up => &self.up,
+            Direction::Down => &self.down,
+        }
+    }
+}
+
+// the anchor in the other room that leads back through our exit number i on side d.
+// if it has a few exits back to us they're matched up in order
+fn back_anchor(exits: &SideExits, d: Direction, i: usize, my_ind: usize, other: &LevelRaw) -> i32 {
+    let ind = exits.get(d)[i];
+    let nth = exits.get(d)[..i].iter().filter(|e| **e == ind).count();
+    let j = other
+        .exits
+        .get(d.opposite())
+        .iter()
+        .enumerate()
+        .filter(|(_, e)| **e == my_ind)
+        .nth(nth)
+        .map(|(j, _)| j)
+        .unwrap_or(0);
+
+    *other
+        .side_offsets()
+        .get(d.opposite())
+        .get(j)
+        .expect("corresponding should have exit anchor")
+}
+
+#[derive(Clone, PartialEq)]
+pub struct LevelRaw {
+    name: String,
+    pub tiles: Vec<Vec<Vec<Tile>>>,
+    exits: SideExits,
+    door_exits: Vec<usize>,
+    // "levelset:room" for each door into another levelset, in order
+    foreign_doors: Vec<String>,
+    theme: Option<usize>,
+    modifiers: Vec<Pickup>,
+    time_limit: Option<i32>,
+    // pages for each sign, in reading order
+    signs: Vec<Vec<String>>,
+    pub meta: LevelMeta,
+}
+
+impl LevelRaw {
+    pub fn side_offsets(&self) -> SideOffsets {
+        let mut side_offsets = SideOffsets::default();
+
+        for layer in self.tiles.iter() {
+            for (y, row) in layer.iter().enumerate() {
+                for (x, tile) in row.iter().enumerate() {
+                    match tile {
+                        Tile::ExitAnchor => {
+                            if x == 0 {
+                                side_offsets.left.push(y as i32 * TILE_SIZE)
+                            } else if x == row.len() - 1 {
+                                side_offsets.right.push(y as i32 * TILE_SIZE)
+                            } else if y == 0 {
+                                side_offsets.up.push(x as i32 * TILE_SIZE)
+                            } else if y == layer.len() - 1 {
+                                side_offsets.down.push(x as i32 * TILE_SIZE)
+                            }
+                        }
+                        _ => (),
+                    }
+                }
+            }
+        }
+        // anchors can be spread over layers
+        side_offsets.left.sort();
+        side_offsets.right.sort();
+        side_offsets.up.sort();
+        side_offsets.down.sort();
+        side_offsets
+    }
+    // every neighbouring room, how far its anchor is shifted from ours, and our anchor
+    fn links(&self, levels: &[LevelRaw], my_ind: usize) -> Vec<(Direction, usize, i32, i32)> {
+        let side_offsets = self.side_offsets();
+        let mut links = vec![];
+        for d in [
+            Direction::Left,
+            Direction::Right,
+            Direction::Up,
+            Direction::Down,
+        ] {
+            for (i, ind) in self.exits.get(d).iter().enumerate() {
+                let mine = *side_offsets
+                    .get(d)
+                    .get(i)
+                    .expect("should have an exit anchor");
+                let theirs = back_anchor(&self.exits, d, i, my_ind, &levels[*ind]);
+                links.push((d, *ind, mine - theirs, mine));
+            }
+        }
+        links
+    }
+    pub fn secret_count(&self) -> i32 {
+        let mut count = 0;
+
+        for l in self.tiles.iter() {
+            for row in l {
+                for col in row {
+                    if *col == Tile::Secret || *col == Tile::ChallengeSecret {
+                        count += 1;
+                    }
+                }
+            }
+        }
+
+        count
+    }
+    pub fn coin_count(&self) -> i32 {
+        count_coins(&self.tiles)
+    }
+    pub fn draw(
         &self,
         off_x: i32,
         off_y: i32,
@@ -1913,100 +3445,36 @@ impl LevelRaw {
                 &themes[self.theme.unwrap_or(default_theme)],
                 gs,
             )
-        }
-        if self.exits.left.is_some() && !seen.contains(&self.exits.left.expect("is some")) {
-            let ind = self.exits.left.expect("is some");
-            seen.push(ind);
-
-            let offset = levels[ind].tiles[0][0].len() as i32 * TILE_PIXELS;
-            let perp_offset = (self.side_offsets().left.expect("is some")
-                - levels[ind]
-                    .side_offsets()
-                    .right
-                    .expect("corresponding should have exit anchor"))
-                / PIXEL_SIZE;
-
-            levels[ind].propagate_draw(
-                off_x - offset,
-                off_y + perp_offset,
-                levels,
-                seen,
-                ind,
-                subs,
-                false,
-                textures,
-                themes,
-                default_theme,
-                gs,
-            );
-        };
-        if self.exits.right.is_some() && !seen.contains(&self.exits.right.expect("is some")) {
-            let ind = self.exits.right.expect("is some");
-            seen.push(ind);
-
-            let offset = self.tiles[0][0].len() as i32 * TILE_PIXELS;
-            let perp_offset = (self.side_offsets().right.expect("is some")
-                - levels[ind]
-                    .side_offsets()
-                    .left
-                    .expect("corresponding should have exit anchor"))
-                / PIXEL_SIZE;
-
-            levels[ind].propagate_draw(
-                off_x + offset,
-                off_y + perp_offset,
-                levels,
-                seen,
-                ind,
-                subs,
-                false,
-                textures,
-                themes,
-                default_theme,
-                gs,
-            );
-        };
-        if self.exits.up.is_some() && !seen.contains(&self.exits.up.expect("is some")) {
-            let ind = self.exits.up.expect("is some");
-            seen.push(ind);
-
-            let offset = levels[ind].tiles[0].len() as i32 * TILE_PIXELS;
-            let perp_offset = (self.side_offsets().up.expect("is some")
-                - levels[ind]
-                    .side_offsets()
-                    .down
-                    .expect("corresponding should have exit anchor"))
-                / PIXEL_SIZE;
-
-            levels[ind].propagate_draw(
-                off_x + perp_offset,
-                off_y - offset,
-                levels,
-                seen,
-                ind,
-                subs,
-                false,
-                textures,
-                themes,
-                default_theme,
-                gs,
-            );
-        };
-        if self.exits.down.is_some() && !seen.contains(&self.exits.down.expect("is some")) {
-            let ind = self.exits.down.expect("is some");
+        }
+        for (d, ind, perp_offset, _) in self.links(levels, my_ind) {
+            if seen.contains(&ind) {
+                continue;
+            }
             seen.push(ind);
 
-            let offset = self.tiles[0].len() as i32 * TILE_PIXELS;
-            let perp_offset = (self.side_offsets().down.expect("is some")
-                - levels[ind]
-                    .side_offsets()
-                    .up
-                    .expect("corresponding should have exit anchor"))
-                / PIXEL_SIZE;
+            let perp_offset = perp_offset / PIXEL_SIZE;
+            let (x, y) = match d {
+                Direction::Left => (
+                    off_x - levels[ind].tiles[0][0].len() as i32 * TILE_PIXELS,
+                    off_y + perp_offset,
+                ),
+                Direction::Right => (
+                    off_x + self.tiles[0][0].len() as i32 * TILE_PIXELS,
+                    off_y + perp_offset,
+                ),
+                Direction::Up => (
+                    off_x + perp_offset,
+                    off_y - levels[ind].tiles[0].len() as i32 * TILE_PIXELS,
+                ),
+                Direction::Down => (
+                    off_x + perp_offset,
+                    off_y + self.tiles[0].len() as i32 * TILE_PIXELS,
+                ),
+            };
 
             levels[ind].propagate_draw(
-                off_x + perp_offset,
-                off_y + offset,
+                x,
+                y,
                 levels,
                 seen,
                 ind,
@@ -2017,9 +3485,11 @@ impl LevelRaw {
                 default_theme,
                 gs,
             );
-        };
+        }
     }
 
+    // draws every visited room reachable from this one and returns the area it took up.
+    // exits into rooms that haven't been visited get a marker instead
     pub fn minimap_draw(
         &self,
         off_x: i32,
@@ -2028,99 +3498,114 @@ impl LevelRaw {
         seen: &mut Vec<usize>,
         my_ind: usize,
         subs: &HashMap<(usize, usize, usize, usize), Tile>,
-    ) {
+        visited: &[usize],
+        skip_actually_drawing: bool,
+    ) -> (i32, i32, i32, i32) {
         let tile_size_thing = 2;
         let (ly, lx) = (self.tiles[0].len(), self.tiles[0][0].len());
-        if seen.len() == 1 {
-            draw_rectangle(
-                off_x as f32 - 1.,
-                off_y as f32 - 1.,
-                (lx * 2 + 2) as f32,
-                (ly * 2 + 2) as f32,
-                color_u8!(255, 255, 255, 31),
-            );
-            draw_rectangle_lines(
-                off_x as f32 - 1.,
-                off_y as f32 - 1.,
-                (lx * 2 + 2) as f32,
-                (ly * 2 + 2) as f32,
-                1.,
-                WHITE,
-            );
-        }
-        for l in self.tiles.iter() {
-            for (y, row) in l.iter().enumerate() {
-                for (x, tile) in row.iter().enumerate() {
-                    draw_rectangle(
-                        (off_x + x as i32 * tile_size_thing) as f32,
-                        (off_y + y as i32 * tile_size_thing) as f32,
-                        tile_size_thing as f32,
-                        tile_size_thing as f32,
-                        tile.minimap_col(),
-                    )
+        let mut area = (
+            off_x,
+            off_y,
+            off_x + lx as i32 * tile_size_thing,
+            off_y + ly as i32 * tile_size_thing,
+        );
+        if !skip_actually_drawing {
+            if seen.len() == 1 {
+                draw_rectangle(
+                    off_x as f32 - 1.,
+                    off_y as f32 - 1.,
+                    (lx * 2 + 2) as f32,
+                    (ly * 2 + 2) as f32,
+                    color_u8!(255, 255, 255, 31),
+                );
+                draw_rectangle_lines(
+                    off_x as f32 - 1.,
+                    off_y as f32 - 1.,
+                    (lx * 2 + 2) as f32,
+                    (ly * 2 + 2) as f32,
+                    1.,
+                    WHITE,
+                );
+            }
+            for (li, l) in self.tiles.iter().enumerate() {
+                for (y, row) in l.iter().enumerate() {
+                    for (x, tile) in row.iter().enumerate() {
+                        let col = match (tile, subs.get(&(my_ind, li, y, x))) {
+                            // found secrets
+                            (Tile::Secret | Tile::ChallengeSecret, Some(Tile::Empty)) => {
+                                color_u8!(255, 103, 255, 255)
+                            }
+                            (_, Some(t)) => t.minimap_col(),
+                            (t, None) => t.minimap_col(),
+                        };
+                        draw_rectangle(
+                            (off_x + x as i32 * tile_size_thing) as f32,
+                            (off_y + y as i32 * tile_size_thing) as f32,
+                            tile_size_thing as f32,
+                            tile_size_thing as f32,
+                            col,
+                        )
+                    }
                 }
             }
         }
-        if self.exits.left.is_some() && !seen.contains(&self.exits.left.expect("is some")) {
-            let ind = self.exits.left.expect("is some");
-            seen.push(ind);
-
-            let offset = levels[ind].tiles[0][0].len() as i32 * tile_size_thing;
-            let perp_offset = (self.side_offsets().left.expect("is some")
-                - levels[ind]
-                    .side_offsets()
-                    .right
-                    .expect("corresponding should have exit anchor"))
-                / TILE_SIZE
-                * tile_size_thing;
-
-            levels[ind].minimap_draw(off_x - offset, off_y + perp_offset, levels, seen, ind, subs);
-        };
-        if self.exits.right.is_some() && !seen.contains(&self.exits.right.expect("is some")) {
-            let ind = self.exits.right.expect("is some");
-            seen.push(ind);
-
-            let offset = self.tiles[0][0].len() as i32 * tile_size_thing;
-            let perp_offset = (self.side_offsets().right.expect("is some")
-                - levels[ind]
-                    .side_offsets()
-                    .left
-                    .expect("corresponding should have exit anchor"))
-                / TILE_SIZE
-                * tile_size_thing;
-
-            levels[ind].minimap_draw(off_x + offset, off_y + perp_offset, levels, seen, ind, subs);
-        };
-        if self.exits.up.is_some() && !seen.contains(&self.exits.up.expect("is some")) {
-            let ind = self.exits.up.expect("is some");
-            seen.push(ind);
-
-            let offset = levels[ind].tiles[0].len() as i32 * tile_size_thing;
-            let perp_offset = (self.side_offsets().up.expect("is some")
-                - levels[ind]
-                    .side_offsets()
-                    .down
-                    .expect("corresponding should have exit anchor"))
-                / TILE_SIZE
-                * tile_size_thing;
+        for (d, ind, perp_offset, mine) in self.links(levels, my_ind) {
+            let perp_offset = perp_offset / TILE_SIZE * tile_size_thing;
+            let (x, y) = match d {
+                Direction::Left => (
+                    off_x - levels[ind].tiles[0][0].len() as i32 * tile_size_thing,
+                    off_y + perp_offset,
+                ),
+                Direction::Right => (
+                    off_x + self.tiles[0][0].len() as i32 * tile_size_thing,
+                    off_y + perp_offset,
+                ),
+                Direction::Up => (
+                    off_x + perp_offset,
+                    off_y - levels[ind].tiles[0].len() as i32 * tile_size_thing,
+                ),
+                Direction::Down => (
+                    off_x + perp_offset,
+                    off_y + self.tiles[0].len() as i32 * tile_size_thing,
+                ),
+            };
 
-            levels[ind].minimap_draw(off_x + perp_offset, off_y - offset, levels, seen, ind, subs);
-        };
-        if self.exits.down.is_some() && !seen.contains(&self.exits.down.expect("is some")) {
-            let ind = self.exits.down.expect("is some");
+            if !visited.contains(&ind) {
+                if !skip_actually_drawing {
+                    let mine = mine / TILE_SIZE * tile_size_thing;
+                    let (mx, my) = match d {
+                        Direction::Left => (off_x - 4, off_y + mine),
+                        Direction::Right => (off_x + lx as i32 * tile_size_thing, off_y + mine),
+                        Direction::Up => (off_x + mine, off_y - 4),
+                        Direction::Down => (off_x + mine, off_y + ly as i32 * tile_size_thing),
+                    };
+                    draw_rectangle(mx as f32, my as f32, 4., 4., YELLOW);
+                }
+                continue;
+            }
+            if seen.contains(&ind) {
+                continue;
+            }
             seen.push(ind);
 
-            let offset = self.tiles[0].len() as i32 * tile_size_thing;
-            let perp_offset = (self.side_offsets().down.expect("is some")
-                - levels[ind]
-                    .side_offsets()
-                    .up
-                    .expect("corresponding should have exit anchor"))
-                / TILE_SIZE
-                * tile_size_thing;
-
-            levels[ind].minimap_draw(off_x + perp_offset, off_y + offset, levels, seen, ind, subs);
-        };
+            let (x1, y1, x2, y2) = levels[ind].minimap_draw(
+                x,
+                y,
+                levels,
+                seen,
+                ind,
+                subs,
+                visited,
+                skip_actually_drawing,
+            );
+            area = (
+                area.0.min(x1),
+                area.1.min(y1),
+                area.2.max(x2),
+                area.3.max(y2),
+            );
+        }
+        area
     }
 
     pub fn find_theme(
@@ -2132,78 +3617,48 @@ impl LevelRaw {
         if self.theme.is_some() {
             return Some((self.theme.expect("is some"), (0, 0)));
         }
-        if self.exits.left.is_some() && !seen.contains(&self.exits.left.expect("is some")) {
-            let ind = self.exits.left.expect("is some");
-            seen.push(ind);
-
-            let offset = levels[ind].tiles[0][0].len() as i32 * TILE_PIXELS;
-            let perp_offset = (self.side_offsets().left.expect("is some")
-                - levels[ind]
-                    .side_offsets()
-                    .right
-                    .expect("corresponding should have exit anchor"))
-                / PIXEL_SIZE;
-
-            let t = levels[ind].find_theme(levels, seen, ind);
-
-            if let Some((t, (x, y))) = t {
-                return Some((t, (x - offset, y + perp_offset)));
-            }
-        };
-        if self.exits.right.is_some() && !seen.contains(&self.exits.right.expect("is some")) {
-            let ind = self.exits.right.expect("is some");
-            seen.push(ind);
-
-            let offset = self.tiles[0][0].len() as i32 * TILE_PIXELS;
-            let perp_offset = (self.side_offsets().right.expect("is some")
-                - levels[ind]
-                    .side_offsets()
-                    .left
-                    .expect("corresponding should have exit anchor"))
-                / PIXEL_SIZE;
-
-            let t = levels[ind].find_theme(levels, seen, ind);
-
-            if let Some((t, (x, y))) = t {
-                return Some((t, (x + offset, y + perp_offset)));
-            }
-        };
-        if self.exits.up.is_some() && !seen.contains(&self.exits.up.expect("is some")) {
-            let ind = self.exits.up.expect("is some");
-            seen.push(ind);
-
-            let offset = levels[ind].tiles[0].len() as i32 * TILE_PIXELS;
-            let perp_offset = (self.side_offsets().up.expect("is some")
-                - levels[ind]
-                    .side_offsets()
-                    .down
-                    .expect("corresponding should have exit anchor"))
-                / PIXEL_SIZE;
-
-            let t = levels[ind].find_theme(levels, seen, ind);
-
-            if let Some((t, (x, y))) = t {
-                return Some((t, (x + perp_offset, y - offset)));
+        for (d, ind, perp_offset, _) in self.links(levels, my_ind) {
+            if seen.contains(&ind) {
+                continue;
             }
-        };
-        if self.exits.down.is_some() && !seen.contains(&self.exits.down.expect("is some")) {
-            let ind = self.exits.down.expect("is some");
             seen.push(ind);
 
-            let offset = self.tiles[0].len() as i32 * TILE_PIXELS;
-            let perp_offset = (self.side_offsets().down.expect("is some")
-                - levels[ind]
-                    .side_offsets()
-                    .up
-                    .expect("corresponding should have exit anchor"))
-                / PIXEL_SIZE;
-
+            let perp_offset = perp_offset / PIXEL_SIZE;
             let t = levels[ind].find_theme(levels, seen, ind);
 
             if let Some((t, (x, y))) = t {
-                return Some((t, (x + perp_offset, y + offset)));
+                return Some(match d {
+                    Direction::Left => (
+                        t,
+                        (
+                            x - levels[ind].tiles[0][0].len() as i32 * TILE_PIXELS,
+                            y + perp_offset,
+                        ),
+                    ),
+                    Direction::Right => (
+                        t,
+                        (
+                            x + self.tiles[0][0].len() as i32 * TILE_PIXELS,
+                            y + perp_offset,
+                        ),
+                    ),
+                    Direction::Up => (
+                        t,
+                        (
+                            x + perp_offset,
+                            y - levels[ind].tiles[0].len() as i32 * TILE_PIXELS,
+                        ),
+                    ),
+                    Direction::Down => (
+                        t,
+                        (
+                            x + perp_offset,
+                            y + self.tiles[0].len() as i32 * TILE_PIXELS,
+                        ),
+                    ),
+                });
             }
-        };
+        }
         None
     }
 }
@@ -2216,12 +3671,60 @@ pub struct Level {
     pub side_offsets: SideOffsets,
     pub theme: usize,
     pub theme_offset: (i32, i32),
+    // forced on for as long as the player is in this room
+    pub modifiers: Vec<Pickup>,
+    // in ticks, counted down from entering the room
+    pub time_limit: Option<i32>,
+    pub signs: Vec<Vec<String>>,
+    pub meta: LevelMeta,
 }
 
 impl Level {
     pub fn dimensions(&self) -> (i32, i32) {
         (self.tiles[0][0].len() as i32, self.tiles[0].len() as i32)
     }
+    pub fn coin_count(&self) -> i32 {
+        count_coins(&self.tiles)
+    }
+    // the exit on side d nearest to pos, as (room, our anchor, their anchor)
+    pub fn exit_towards(
+        &self,
+        d: Direction,
+        pos: i32,
+        levels: &[LevelRaw],
+        my_ind: usize,
+    ) -> Option<(usize, i32, i32)> {
+        let exits = self.side_exits.get(d);
+        let offsets = self.side_offsets.get(d);
+        let i = (0..exits.len())
+            .min_by_key(|i| (offsets.get(*i).expect("should have an exit anchor") - pos).abs())?;
+        let theirs = back_anchor(&self.side_exits, d, i, my_ind, &levels[exits[i]]);
+
+        Some((exits[i], offsets[i], theirs))
+    }
+    // true when the time ran out and there's no fail gate to let the player off
+    pub fn tick_challenge(&mut self, global_state: &mut GlobalState) -> bool {
+        match global_state.challenge_timer {
+            Some(t) if t > 0 => {
+                global_state.challenge_timer = Some(t - 1);
+                false
+            }
+            Some(_) => {
+                global_state.challenge_timer = None;
+                for layer in self.tiles.iter_mut() {
+                    for row in layer.iter_mut() {
+                        for tile in row.iter_mut() {
+                            if *tile == Tile::ChallengeSecret {
+                                *tile = Tile::Empty;
+                            }
+                        }
+                    }
+                }
+                open_fail_gates(&mut self.tiles).is_empty()
+            }
+            None => false,
+        }
+    }
     pub fn focus_position(&self) -> (i32, i32) {
         for obj in self.objects.iter() {
             if obj.get_type() == "PLAYER" {
@@ -2318,6 +3821,8 @@ impl Level {
         sounds: &mut HashMap<String, Sound>,
     ) {
         global_state.timer += 1;
+        global_state.tick_modifiers();
+        global_state.focus = self.focus_position();
         for o in self.objects.iter_mut() {
             o.update(keys_pressed, &mut self.tiles, global_state, sounds)
         }
@@ -2339,13 +3844,9 @@ impl Level {
     ) -> Self {
         let mut tiles = vec![];
         let mut objects: Vec<Box<dyn Object>> = vec![];
-        let mut side_offsets = SideOffsets {
-            left: None,
-            right: None,
-            up: None,
-            down: None,
-        };
+        let side_offsets = l.side_offsets();
         let mut door_exits = l.door_exits.iter();
+        let mut sign_ind = 0;
 
         for (la, layer) in l.tiles.iter().enumerate() {
             let mut l_tiles = vec![];
@@ -2366,20 +3867,19 @@ impl Level {
                                 wall_sliding: 0,
                                 anim_timer: 0,
                                 air_frames: 0,
+                                jump_buffer: 0,
+                                wall_grace: 0,
+                                last_wall: 0,
+                                sticky_wall: false,
+                                on_slope: false,
+                                pounding: false,
+                                pound_landed: false,
+                                shockwave: 0,
                             };
                             objects.push(Box::new(obj));
                             row_tiles.push(Tile::Empty);
                         }
                         Tile::ExitAnchor => {
-                            if x == 0 {
-                                side_offsets.left = Some(y as i32 * TILE_SIZE)
-                            } else if x == row.len() - 1 {
-                                side_offsets.right = Some(y as i32 * TILE_SIZE)
-                            } else if y == 0 {
-                                side_offsets.up = Some(x as i32 * TILE_SIZE)
-                            } else if y == layer.len() - 1 {
-                                side_offsets.down = Some(x as i32 * TILE_SIZE)
-                            }
                             row_tiles.push(Tile::Empty);
                         }
                         Tile::DoorGeneric => {
@@ -2394,6 +3894,11 @@ impl Level {
                                 .expect("should have a corresponding door entrance");
                             row_tiles.push(Tile::SecretDoor(*ind));
                         }
+                        Tile::SignGeneric => {
+                            assert!(sign_ind < l.signs.len(), "sign should have text");
+                            row_tiles.push(Tile::Sign(sign_ind));
+                            sign_ind += 1;
+                        }
                         Tile::SawLauncherLeft
                         | Tile::SawLauncherRight
                         | Tile::SawLauncherUp
@@ -2412,6 +3917,7 @@ impl Level {
                                 vx,
                                 vy,
                                 frames: 45,
+                                bounces: 0,
                             }));
 
                             row_tiles.push(*newt)
@@ -2434,10 +3940,57 @@ impl Level {
                                 vx,
                                 vy,
                                 frames: 32,
+                                bounces: 0,
+                            }));
+
+                            row_tiles.push(*newt)
+                        }
+                        Tile::BouncySawLauncherLeft
+                        | Tile::BouncySawLauncherRight
+                        | Tile::BouncySawLauncherUp
+                        | Tile::BouncySawLauncherDown => {
+                            let (vx, vy) = match &newt {
+                                Tile::BouncySawLauncherLeft => (-TILE_SIZE / 8, 0),
+                                Tile::BouncySawLauncherRight => (TILE_SIZE / 8, 0),
+                                Tile::BouncySawLauncherUp => (0, -TILE_SIZE / 8),
+                                Tile::BouncySawLauncherDown => (0, TILE_SIZE / 8),
+                                _ => unreachable!(),
+                            };
+
+                            objects.push(Box::new(SawLauncher {
+                                x: x as i32 * TILE_SIZE,
+                                y: y as i32 * TILE_SIZE,
+                                vx,
+                                vy,
+                                frames: BOUNCY_SAW_FRAMES,
+                                bounces: BOUNCY_SAW_BOUNCES,
+                            }));
+
+                            row_tiles.push(*newt)
+                        }
+                        Tile::Turret => {
+                            objects.push(Box::new(Turret {
+                                x: x as i32 * TILE_SIZE,
+                                y: y as i32 * TILE_SIZE,
+                                cooldown: 60,
+                                windup: 0,
+                                shot: None,
                             }));
 
                             row_tiles.push(*newt)
                         }
+                        Tile::Stalactite => {
+                            objects.push(Box::new(Stalactite {
+                                x: x as i32 * TILE_SIZE,
+                                y: y as i32 * TILE_SIZE,
+                                vy: 0,
+                                shake_timer: 0,
+                                falling: false,
+                                shatter_timer: 0,
+                            }));
+
+                            row_tiles.push(Tile::Empty)
+                        }
                         Tile::JumpArrow => {
                             row_tiles.push(Tile::JumpArrow);
                             objects.push(Box::new(ArrowRespawn {
@@ -2466,6 +4019,16 @@ impl Level {
                 .unwrap_or((0, (0, 0))),
         };
 
+        // once the challenge secret has been picked up, the room is beaten for good
+        let beaten = l.tiles.iter().enumerate().any(|(la, layer)| {
+            layer.iter().enumerate().any(|(y, row)| {
+                row.iter().enumerate().any(|(x, tile)| {
+                    *tile == Tile::ChallengeSecret
+                        && subs.get(&(my_ind, la, y, x)) == Some(&Tile::Empty)
+                })
+            })
+        });
+
         Level {
             name: l.name,
             tiles,
@@ -2474,22 +4037,44 @@ impl Level {
             side_offsets,
             theme: theme.0,
             theme_offset: theme.1,
+            modifiers: l.modifiers,
+            time_limit: if beaten { None } else { l.time_limit },
+            signs: l.signs,
+            meta: l.meta,
         }
     }
 }
 
-fn load_level(path: &str, level_inds: &HashMap<&str, usize>) -> LevelRaw {
-    let level_contents = std::fs::read_to_string(path).unwrap();
-    let level_contents = level_contents.trim().replace("\r\n", "\n");
+#[derive(Clone, Default, PartialEq)]
+pub struct LevelMeta {
+    pub author: Option<String>,
+    pub description: Option<String>,
+    // in ticks
+    pub par_time: Option<i32>,
+    // a path, or "none" for silence
+    pub music: Option<String>,
+}
 
-    let mut parts = level_contents.split("\n===\n");
+// everything from the links and meta sections
+#[derive(Default)]
+struct LevelHeader {
+    exits: SideExits,
+    door_exits: Vec<usize>,
+    foreign_doors: Vec<String>,
+    theme: Option<usize>,
+    modifiers: Vec<Pickup>,
+    time_limit: Option<i32>,
+    signs: Vec<Vec<String>>,
+    meta: LevelMeta,
+}
 
-    let name = parts.next().expect("should have part").to_string();
+const LEVEL_V2: &str = "nmllevel 2";
+const LEVEL_SECTIONS: [&str; 6] = ["name", "meta", "legend", "links", "signs", "layer"];
 
-    let tilemap: HashMap<char, Tile> = parts
-        .next()
-        .expect("balls")
+fn parse_legend(section: &str) -> HashMap<char, Tile> {
+    section
         .lines()
+        .filter(|a| !a.trim().is_empty())
         .map(|a| {
             // println!("{}", a);
             let mut halves = a.split(":");
@@ -2506,86 +4091,334 @@ fn load_level(path: &str, level_inds: &HashMap<&str, usize>) -> LevelRaw {
 
             (left_half, right_half)
         })
-        .collect();
+        .collect()
+}
 
-    let (exits, door_exits, theme) = {
-        let mut exits = SideExits {
-            left: None,
-            right: None,
-            up: None,
-            down: None,
+fn parse_header(header: &mut LevelHeader, section: &str, level_inds: &HashMap<&str, usize>) {
+    for l in section.lines() {
+        if l.trim().is_empty() {
+            continue;
+        }
+        // sign text can have colons in it
+        let mut halves = l.splitn(2, ":");
+        let left_half = halves.next().expect("should have two halves").trim();
+
+        let right_half = halves.next().expect("should have two halves").trim();
+        match left_half {
+            "sign" => {
+                header.signs.push(
+                    right_half
+                        .split("|")
+                        .map(|p| p.trim().to_string())
+                        .collect(),
+                );
+                continue;
+            }
+            "modifier" => {
+                header
+                    .modifiers
+                    .push(Pickup::from_name(right_half).expect("should be a modifier"));
+                continue;
+            }
+            "time_limit" => {
+                header.time_limit =
+                    Some(right_half.parse().expect("time limit should be in ticks"));
+                continue;
+            }
+            "author" => {
+                header.meta.author = Some(right_half.to_string());
+                continue;
+            }
+            "description" => {
+                header.meta.description = Some(right_half.to_string());
+                continue;
+            }
+            "par" => {
+                header.meta.par_time = Some(right_half.parse().expect("par should be in ticks"));
+                continue;
+            }
+            "music" => {
+                header.meta.music = Some(right_half.to_string());
+                continue;
+            }
+            // doors into another levelset get their index once the whole set is loaded
+            "door" if right_half.contains(':') => {
+                header.door_exits.push(usize::MAX);
+                header.foreign_doors.push(right_half.to_string());
+                continue;
+            }
+            _ => (),
+        }
+        let right_half: usize = match right_half.parse() {
+            Ok(i) => i,
+            Err(_) => *level_inds.get(right_half).expect("should exist"),
         };
-        let mut door_exits = vec![];
-        let mut theme = None;
 
-        for l in parts.next().expect("should have part").lines() {
-            let mut halves = l.split(":");
-            let left_half = halves.next().expect("should have two halves").trim();
+        match left_half {
+            "left" => header.exits.left.push(right_half),
+            "right" => header.exits.right.push(right_half),
+            "up" => header.exits.up.push(right_half),
+            "down" => header.exits.down.push(right_half),
+            "door" => header.door_exits.push(right_half),
+            "theme" => header.theme = Some(right_half),
+            _ => (),
+        }
+    }
+}
 
-            let right_half = halves.next().expect("should have two halves").trim();
-            let right_half: usize = match right_half.parse() {
-                Ok(i) => i,
-                Err(_) => *level_inds.get(right_half).expect("should exist"),
-            };
+fn parse_layer(section: &str, tilemap: &HashMap<char, Tile>) -> Vec<Vec<Tile>> {
+    let mut layer = vec![];
+    for row in section.lines() {
+        let row = row.chars();
+        let row = row
+            .map(|c| *tilemap.get(&c).unwrap_or(&Tile::Empty))
+            .collect();
+        layer.push(row);
+    }
+    layer
+}
 
-            match left_half {
-                "left" => exits.left = Some(right_half),
-                "right" => exits.right = Some(right_half),
-                "up" => exits.up = Some(right_half),
-                "down" => exits.down = Some(right_half),
-                "door" => door_exits.push(right_half),
-                "theme" => theme = Some(right_half),
-                _ => (),
-            }
+// v2 files are split up by "=== <section>" lines, so the order doesn't matter
+fn split_sections(level_contents: &str) -> Vec<(&str, String)> {
+    let mut sections: Vec<(&str, String)> = vec![];
+    for l in level_contents.lines().skip(1) {
+        if l.starts_with("===") {
+            let name = l
+                .strip_prefix("=== ")
+                .map(|n| n.trim())
+                .filter(|n| LEVEL_SECTIONS.contains(n))
+                .unwrap_or_else(|| {
+                    panic!(
+                        "\"{}\" should be \"=== <section>\" with a section out of {}",
+                        l,
+                        LEVEL_SECTIONS.join(", ")
+                    )
+                });
+            sections.push((name, String::new()));
+        } else if let Some((_, body)) = sections.last_mut() {
+            body.push_str(l);
+            body.push('\n');
+        }
+    }
+    // blank lines around a layer would otherwise turn into rows with no tiles
+    for (name, body) in sections.iter_mut() {
+        if *name == "layer" {
+            let rows: Vec<&str> = body.lines().collect();
+            let first = rows.iter().position(|r| !r.trim().is_empty()).unwrap_or(0);
+            let last = rows
+                .iter()
+                .rposition(|r| !r.trim().is_empty())
+                .map_or(0, |i| i + 1);
+            *body = rows[first..last.max(first)].join("\n");
         }
+    }
+    sections
+}
 
-        (exits, door_exits, theme)
-    };
+fn load_level(path: &str, level_inds: &HashMap<&str, usize>) -> LevelRaw {
+    let level_contents = pack::read_to_string(path).expect("level should exist");
+    parse_level(&level_contents, level_inds)
+}
+
+pub fn parse_level(level_contents: &str, level_inds: &HashMap<&str, usize>) -> LevelRaw {
+    let level_contents = level_contents.trim().replace("\r\n", "\n");
+
+    let mut header = LevelHeader::default();
+
+    let (name, tiles) = if level_contents.starts_with(LEVEL_V2) {
+        let sections = split_sections(&level_contents);
+        let section = |n: &str| {
+            sections
+                .iter()
+                .find(|(name, _)| *name == n)
+                .map(|(_, body)| body.as_str())
+                .unwrap_or("")
+        };
+
+        let name = section("name").trim().to_string();
+        let tilemap = parse_legend(section("legend"));
+        parse_header(&mut header, section("meta"), level_inds);
+        parse_header(&mut header, section("links"), level_inds);
+        parse_header(&mut header, section("signs"), level_inds);
+        let tiles = sections
+            .iter()
+            .filter(|(name, _)| *name == "layer")
+            .map(|(_, body)| parse_layer(body, &tilemap))
+            .collect();
+
+        (name, tiles)
+    } else {
+        let mut parts = level_contents.split("\n===\n");
+
+        let name = parts.next().expect("should have part").to_string();
 
-    let mut tiles = vec![];
-    while let Some(layer_content) = parts.next() {
-        let mut layer = vec![];
-        for row in layer_content.lines() {
-            let row = row.chars();
-            let row = row
-                .map(|c| *tilemap.get(&c).unwrap_or(&Tile::Empty))
-                .collect();
-            layer.push(row);
-        }
-        tiles.push(layer)
-    }
-
-    // println!("{:?}", tiles);
-    // let tiles: Vec<Vec<Tile>> = parts
-    //     .next()
-    //     .expect("balls")
-    //     .lines()
-    //     .map(|l| {
-    //         l.chars().collect::<Vec<char>>()[..]
-    //             .chunks(2)
-    //             .map(|pair| Tile::from_chars(pair[0], pair[1]))
-    //             .collect()
-    //     })
-    //     .collect();
+        let tilemap = parse_legend(parts.next().expect("balls"));
+
+        parse_header(
+            &mut header,
+            parts.next().expect("should have part"),
+            level_inds,
+        );
+
+        let mut tiles = vec![];
+        for layer_content in parts {
+            tiles.push(parse_layer(layer_content, &tilemap))
+        }
+
+        (name, tiles)
+    };
 
     LevelRaw {
         name,
         tiles,
-        exits,
-        door_exits,
-        theme,
+        exits: header.exits,
+        door_exits: header.door_exits,
+        foreign_doors: header.foreign_doors,
+        theme: header.theme,
+        modifiers: header.modifiers,
+        time_limit: header.time_limit,
+        signs: header.signs,
+        meta: header.meta,
+    }
+}
+
+// rewrites a v1 .lvl file as v2. returns false if it was already v2
+pub fn convert_level(path: &str) -> bool {
+    let level_contents = std::fs::read_to_string(path).unwrap();
+    let level_contents = level_contents.trim().replace("\r\n", "\n");
+    if level_contents.starts_with(LEVEL_V2) {
+        return false;
+    }
+
+    let mut parts = level_contents.split("\n===\n");
+    let name = parts.next().expect("should have part");
+    let legend = parts.next().expect("should have part");
+
+    let mut meta = String::new();
+    let mut links = String::new();
+    let mut signs = String::new();
+    for l in parts.next().expect("should have part").lines() {
+        let key = l.split(":").next().unwrap_or("").trim();
+        let section = match key {
+            "modifier" | "time_limit" | "author" | "description" | "par" | "music" => &mut meta,
+            "sign" => &mut signs,
+            _ => &mut links,
+        };
+        section.push_str(l);
+        section.push('\n');
     }
+
+    let mut out = format!("{}\n=== name\n{}\n", LEVEL_V2, name);
+    if !meta.is_empty() {
+        out.push_str(&format!("=== meta\n{}", meta));
+    }
+    out.push_str(&format!("=== legend\n{}\n=== links\n{}", legend, links));
+    if !signs.is_empty() {
+        out.push_str(&format!("=== signs\n{}", signs));
+    }
+    for layer in parts {
+        out.push_str(&format!("=== layer\n{}\n", layer));
+    }
+
+    // make sure nothing got lost before overwriting the original. rooms are linked by name,
+    // so give every name in the header a made up index, the same for both
+    let level_inds: HashMap<&str, usize> = level_contents
+        .split("\n===\n")
+        .nth(2)
+        .unwrap_or("")
+        .lines()
+        .filter_map(|l| Some(l.split_once(':')?.1.trim()))
+        .enumerate()
+        .map(|(i, n)| (n, i))
+        .collect();
+    if parse_level(&level_contents, &level_inds) != parse_level(&out, &level_inds) {
+        panic!(
+            "{} doesn't load the same after converting, leaving it alone",
+            path
+        );
+    }
+
+    std::fs::write(path, out).expect("should be able to write level");
+    true
 }
 
 pub struct Levelset {
     pub name: String,
     pub levels: Vec<LevelRaw>,
+    // file names of the rooms, as written in levels.levelset
+    pub room_names: Vec<String>,
+    // doors with an index past the end of levels lead to (levelset, room) in here
+    pub foreign_rooms: Vec<(String, String)>,
     pub themes: Vec<Theme>,
     pub secret_count: i32,
+    pub coin_count: i32,
+    pub physics: Physics,
+}
+
+impl Levelset {
+    pub fn foreign_room(&self, door: usize) -> Option<&(String, String)> {
+        self.foreign_rooms.get(door.checked_sub(self.levels.len())?)
+    }
+    pub fn room_index(&self, room: &str) -> usize {
+        self.room_names
+            .iter()
+            .position(|n| n == room)
+            .expect("door should lead to a room in the levelset")
+    }
+    // the door index that leads back to a room in another levelset
+    pub fn door_to(&self, set: &str, room: &str) -> Option<usize> {
+        self.foreign_rooms
+            .iter()
+            .position(|(s, r)| s == set && r == room)
+            .map(|i| self.levels.len() + i)
+    }
+}
+
+// what has to be done before a levelset can be played
+#[derive(Clone, Debug)]
+pub enum Unlock {
+    Complete(String),
+    // across every levelset
+    Secrets(i32),
+}
+
+impl Unlock {
+    pub fn description(&self) -> String {
+        match self {
+            Unlock::Complete(set) => format!("beat {} to unlock", set),
+            Unlock::Secrets(n) => format!("find {} secrets to unlock", n),
+        }
+    }
+}
+
+// unlocks are the section after themes in levels.levelset, like
+//   complete: tutorial
+//   secrets: 5
+// this only reads the levelset file, so the level select can check them cheaply
+pub fn load_unlocks(path: &str) -> Vec<Unlock> {
+    let levelset_file =
+        pack::read_to_string(&format!("{}/levels.levelset", path)).expect("levelset should exist");
+    let levelset_file = levelset_file.trim().replace("\r\n", "\n");
+
+    let mut unlocks = vec![];
+    for l in levelset_file.split("\n===\n").nth(3).unwrap_or("").lines() {
+        let Some((key, val)) = l.split_once(":") else {
+            continue;
+        };
+        match key.trim() {
+            "complete" => unlocks.push(Unlock::Complete(val.trim().to_string())),
+            "secrets" => unlocks.push(Unlock::Secrets(
+                val.trim().parse().expect("should be a number of secrets"),
+            )),
+            _ => (),
+        }
+    }
+    unlocks
 }
 
 pub fn load_levelset(path: &str) -> Levelset {
-    let levelset_file = std::fs::read_to_string(format!("{}/levels.levelset", path)).unwrap();
+    let levelset_file =
+        pack::read_to_string(&format!("{}/levels.levelset", path)).expect("levelset should exist");
     let levelset_file = levelset_file.trim().replace("\r\n", "\n");
 
     // println!("{}", levelset_file);
@@ -2595,6 +4428,7 @@ pub fn load_levelset(path: &str) -> Levelset {
     let name = parts.next().expect("should have part").to_string();
 
     let mut secret_count = 0;
+    let mut coin_count = 0;
 
     let level_names: Vec<&str> = parts
         .next()
@@ -2610,16 +4444,64 @@ pub fn load_levelset(path: &str) -> Levelset {
         .collect();
 
     let mut levels = vec![];
-    for l in level_names {
+    for l in level_names.iter() {
         // println!("reading {}/{}.lvl", path, l);
 
-        let lev = load_level(&format!("{}/{}.lvl", path, l), &level_inds);
+        // rooms can also come straight from a Tiled map
+        let lvl_path = format!("{}/{}.lvl", path, l);
+        let mut lev = match tiled::find_map(&format!("{}/{}", path, l)) {
+            Some(map_path) if !pack::exists(&lvl_path) => tiled::import(&map_path, &level_inds),
+            _ => load_level(&lvl_path, &level_inds),
+        };
+        if let Some(music) = &lev.meta.music {
+            lev.meta.music = Some(pack::resolve(&lvl_path, music));
+        }
 
         secret_count += lev.secret_count();
+        coin_count += lev.coin_count();
 
         levels.push(lev);
     }
 
+    let mut foreign_rooms: Vec<(String, String)> = vec![];
+    let level_count = levels.len();
+    for lev in levels.iter_mut() {
+        let mut targets = lev.foreign_doors.iter();
+        for d in lev.door_exits.iter_mut().filter(|d| **d == usize::MAX) {
+            let (set, room) = targets
+                .next()
+                .expect("should have a target")
+                .split_once(':')
+                .expect("should be levelset:room");
+            let target = (set.trim().to_string(), room.trim().to_string());
+            *d = level_count
+                + match foreign_rooms.iter().position(|r| *r == target) {
+                    Some(i) => i,
+                    None => {
+                        foreign_rooms.push(target);
+                        foreign_rooms.len() - 1
+                    }
+                };
+        }
+    }
+    // a typo in a door should stop the levelset loading, not the game when someone walks through
+    for (set, room) in foreign_rooms.iter() {
+        let set_path = levelset_path(set);
+        if !std::path::Path::new(&set_path).exists() {
+            panic!("door leads to levelset {} which doesn't exist", set);
+        }
+        let set_file = pack::read_to_string(&format!("{}/levels.levelset", set_path))
+            .expect("levelset should exist");
+        let set_file = set_file.trim().replace("\r\n", "\n");
+        let rooms = set_file.split("\n===\n").nth(1).unwrap_or("");
+        if !rooms.lines().any(|n| n.trim() == room) {
+            panic!(
+                "door leads to {}:{} but {} has no room by that name",
+                set, room, set
+            );
+        }
+    }
+
     let mut themes = vec![];
     let np = parts.next();
     if np.is_some() {
@@ -2636,10 +4518,22 @@ pub fn load_levelset(path: &str) -> Levelset {
         }
     }
 
+    // levelsets can tune how the player moves
+    let physics_path = format!("{}/physics.nmlphysics", path);
+    let physics = if pack::exists(&physics_path) {
+        Physics::from_path(&physics_path)
+    } else {
+        Physics::default()
+    };
+
     Levelset {
         name,
         levels,
+        room_names: level_names.iter().map(|n| n.to_string()).collect(),
+        foreign_rooms,
         themes,
         secret_count,
+        coin_count,
+        physics,
     }
 }
@@ -15,7 +15,7 @@ macro_rules! texture {
 					break
 				} else {
 
-          let t = load_texture(path).await.unwrap();
+          let t = Texture2D::from_file_with_format(&$crate::pack::read(path).expect("texture should exist"), None);
           $textures.insert(path.to_string(), t.clone());
 					t.set_filter(FilterMode::Nearest);
           texture = Some(t);
@@ -73,7 +73,7 @@ macro_rules! sound {
 					break
 				} else {
 
-          let t = load_sound(path).await.unwrap();
+          let t = load_sound_from_bytes(&$crate::pack::read(path).expect("sound should exist")).await.unwrap();
           $sounds.insert(path.to_string(), t.clone());
           sound = Some(t);
 					// std::thread::sleep(std::time::Duration::from_millis(16));
@@ -106,7 +106,7 @@ macro_rules! raw_sound {
 		// $(, $paths:expr)+
 		$path: expr
 	) => {
-        let d = std::fs::read(&$path).expect("should exist");
+        let d = $crate::pack::read(&$path).expect("should exist");
         let t = RawSound::load($ctx, &d);
         $sounds.insert($path.to_string(), t);
         // std::thread::sleep(std::time::Duration::from_millis(16));
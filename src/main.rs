@@ -3,7 +3,7 @@
 use core::f32;
 use std::collections::HashMap;
 
-use macroquad::audio::{load_sound, play_sound, PlaySoundParams, Sound};
+use macroquad::audio::{load_sound_from_bytes, play_sound, PlaySoundParams, Sound};
 use macroquad::prelude::*;
 use quad_snd::{AudioContext, Playback, Sound as RawSound};
 
@@ -14,6 +14,15 @@ const TILE_SIZE: i32 = TILE_PIXELS * PIXEL_SIZE;
 const MAX_PLAYER_SPEED: i32 = TILE_SIZE * 3 / 16;
 const PLAYER_ACCEL: i32 = TILE_SIZE / 16;
 
+// how forgiving jumping is, in ticks
+const COYOTE_FRAMES: i32 = 15;
+const WALL_COYOTE_FRAMES: i32 = 6;
+const JUMP_BUFFER_FRAMES: i32 = 6;
+
+// bouncy saws come out less often, and break after this many walls
+const BOUNCY_SAW_FRAMES: i32 = 90;
+const BOUNCY_SAW_BOUNCES: i32 = 6;
+
 const SCREEN_WIDTH: i32 = 640;
 const SCREEN_HEIGHT: i32 = 368;
 
@@ -22,6 +31,9 @@ use levels::Object;
 
 mod macros;
 
+mod pack;
+mod tiled;
+
 enum MenuState {
     Main(usize),
     LevelsetSelect(usize, usize, levels::Modifiers),
@@ -43,6 +55,23 @@ enum State {
     },
 }
 
+// a levelset the player left through a door, kept as it was for when they come back
+struct SetProgress {
+    levelset: levels::Levelset,
+    global_state: levels::GlobalState,
+    deaths: i32,
+}
+
+// door targets name levelsets like the level select does, packs without the extension
+fn levelset_path(set: &str) -> String {
+    let folder = format!("levels/{}", set);
+    if pack::exists(&format!("{}/levels.levelset", folder)) {
+        folder
+    } else {
+        format!("{}{}", folder, pack::PACK_EXT)
+    }
+}
+
 #[derive(Clone)]
 struct BackgroundLayer {
     image: String,
@@ -75,11 +104,17 @@ struct Theme {
     back_wall_4: Option<String>,
 
     oneway: Option<String>,
+    // overrides for how each wall kind behaves, see Theme::surfaces
+    wall_surfaces: [Option<levels::Surface>; 4],
+    slope: Option<String>,
+    shapes: Option<String>,
+    stalactite: Option<String>,
+    stalactite_shatter: Option<String>,
 }
 
 impl Theme {
     fn from_path(path: &str) -> Self {
-        let s = std::fs::read_to_string(path).unwrap();
+        let s = pack::read_to_string(path).expect("theme should exist");
         let s = s.trim().replace("\r\n", "\n");
 
         let mut theme = Theme {
@@ -92,7 +127,7 @@ impl Theme {
                 // println!("{:?}", lines);
                 lines.next();
                 theme.bg.push(BackgroundLayer {
-                    image: lines.next().expect("should exist").into(),
+                    image: pack::resolve(path, lines.next().expect("should exist")),
 
                     off_x: lines.next().expect("sh").parse().expect("sh"),
                     off_y: lines.next().expect("sh").parse().expect("sh"),
@@ -113,20 +148,38 @@ impl Theme {
                         parts.next().expect("should exist"),
                         parts.next().expect("should exist"),
                     );
+                    let file = pack::resolve(path, b.trim());
                     match a.trim() {
-                        "wall_1" => theme.wall_1 = Some(b.trim().into()),
-                        "wall_2" => theme.wall_2 = Some(b.trim().into()),
-                        "wall_3" => theme.wall_3 = Some(b.trim().into()),
-                        "wall_4" => theme.wall_4 = Some(b.trim().into()),
+                        "wall_1" => theme.wall_1 = Some(file.clone()),
+                        "wall_2" => theme.wall_2 = Some(file.clone()),
+                        "wall_3" => theme.wall_3 = Some(file.clone()),
+                        "wall_4" => theme.wall_4 = Some(file.clone()),
 
-                        "back_wall_1" => theme.back_wall_1 = Some(b.trim().into()),
-                        "back_wall_2" => theme.back_wall_2 = Some(b.trim().into()),
-                        "back_wall_3" => theme.back_wall_3 = Some(b.trim().into()),
-                        "back_wall_4" => theme.back_wall_4 = Some(b.trim().into()),
+                        "back_wall_1" => theme.back_wall_1 = Some(file.clone()),
+                        "back_wall_2" => theme.back_wall_2 = Some(file.clone()),
+                        "back_wall_3" => theme.back_wall_3 = Some(file.clone()),
+                        "back_wall_4" => theme.back_wall_4 = Some(file.clone()),
 
-                        "oneway" => theme.oneway = Some(b.trim().into()),
+                        "oneway" => theme.oneway = Some(file.clone()),
 
-                        "mus" => theme.mus = Some(b.trim().into()),
+                        "wall_1_surface" => {
+                            theme.wall_surfaces[0] = Some(levels::Surface::from_string(b))
+                        }
+                        "wall_2_surface" => {
+                            theme.wall_surfaces[1] = Some(levels::Surface::from_string(b))
+                        }
+                        "wall_3_surface" => {
+                            theme.wall_surfaces[2] = Some(levels::Surface::from_string(b))
+                        }
+                        "wall_4_surface" => {
+                            theme.wall_surfaces[3] = Some(levels::Surface::from_string(b))
+                        }
+                        "slope" => theme.slope = Some(file.clone()),
+                        "shapes" => theme.shapes = Some(file.clone()),
+                        "stalactite" => theme.stalactite = Some(file.clone()),
+                        "stalactite_shatter" => theme.stalactite_shatter = Some(file.clone()),
+
+                        "mus" => theme.mus = Some(file.clone()),
 
                         _ => (),
                     }
@@ -137,6 +190,34 @@ impl Theme {
         theme
     }
 
+    // wall4 is slippery and can't be grabbed unless a theme says otherwise
+    fn surfaces(&self) -> [levels::Surface; 4] {
+        let mut surfaces = [levels::Surface::default(); 4];
+        surfaces[3] = levels::Surface {
+            ice: true,
+            nogrip: true,
+            sticky: false,
+        };
+        for (s, o) in surfaces.iter_mut().zip(self.wall_surfaces.iter()) {
+            if let Some(o) = o {
+                *s = *o;
+            }
+        }
+        surfaces
+    }
+
+    // stalactite and its shatter frames, so icy themes can have icy ones
+    fn stalactite_sprites(&self) -> (String, String) {
+        (
+            self.stalactite
+                .clone()
+                .unwrap_or("assets/stalactite.png".into()),
+            self.stalactite_shatter
+                .clone()
+                .unwrap_or("assets/stalactiteshatter.png".into()),
+        )
+    }
+
     async fn load_textures(&self, textures: &mut HashMap<String, Texture2D>) {
         for t in self.bg.iter() {
             texture!(textures, &t.image);
@@ -170,6 +251,18 @@ impl Theme {
         if self.oneway.is_some() {
             texture!(textures, self.oneway.as_ref().expect("is some"));
         }
+        if let Some(slope) = &self.slope {
+            texture!(textures, slope);
+        }
+        if let Some(shapes) = &self.shapes {
+            texture!(textures, shapes);
+        }
+        if let Some(stalactite) = &self.stalactite {
+            texture!(textures, stalactite);
+        }
+        if let Some(stalactite_shatter) = &self.stalactite_shatter {
+            texture!(textures, stalactite_shatter);
+        }
     }
 }
 
@@ -386,7 +479,7 @@ fn draw_text_cool(tx: &Texture2D, t: &str, x: i32, y: i32, c: Color) {
     }
 }
 
-fn draw_text_cool_c(tx: &Texture2D, t: &str, x: i32, y: i32, c: Color) {
+fn text_width(t: &str) -> i32 {
     let mut total_width = 0;
     for ch in t.chars() {
         let ind = ch as u32;
@@ -399,21 +492,17 @@ fn draw_text_cool_c(tx: &Texture2D, t: &str, x: i32, y: i32, c: Color) {
         total_width += 12 - kern * 2;
     }
 
+    total_width
+}
+
+fn draw_text_cool_c(tx: &Texture2D, t: &str, x: i32, y: i32, c: Color) {
+    let total_width = text_width(t);
+
     draw_text_cool(tx, t, x - total_width / 2, y, c);
 }
 
 fn draw_text_cool_l(tx: &Texture2D, t: &str, x: i32, y: i32, c: Color) {
-    let mut total_width = 0;
-    for ch in t.chars() {
-        let ind = ch as u32;
-        let ind = if ind >= 32 && ind <= 127 {
-            ind - 32
-        } else {
-            95
-        };
-        let kern = FONT_KERN[ind as usize];
-        total_width += 12 - kern * 2;
-    }
+    let total_width = text_width(t);
 
     draw_text_cool(tx, t, x - total_width, y, c);
 }
@@ -567,6 +656,60 @@ impl Settings {
     }
 }
 
+// what the player has done in each levelset, kept between sessions
+#[derive(Default)]
+struct Progress {
+    // levelset to (completed, most secrets found in one go)
+    sets: HashMap<String, (bool, i32)>,
+}
+impl Progress {
+    fn load(path: &str) -> Self {
+        let mut progress = Progress::default();
+        let Ok(s) = std::fs::read_to_string(path) else {
+            return progress;
+        };
+
+        // tutorial: true 3
+        for l in s.lines() {
+            let Some((set, val)) = l.split_once(": ") else {
+                continue;
+            };
+            let mut parts = val.split_whitespace();
+            let completed = parts.next() == Some("true");
+            let secrets = parts.next().and_then(|n| n.parse().ok()).unwrap_or(0);
+            progress.sets.insert(set.to_string(), (completed, secrets));
+        }
+
+        progress
+    }
+    fn save(path: &str, p: &Progress) {
+        let mut sets: Vec<_> = p.sets.iter().collect();
+        sets.sort_by_key(|(set, _)| *set);
+
+        let mut output_str = "".to_string();
+        for (set, (completed, secrets)) in sets {
+            output_str.push_str(&format!("{}: {} {}\n", set, completed, secrets));
+        }
+
+        let _ = std::fs::write(path, &output_str);
+    }
+    fn record(&mut self, set: &str, completed: bool, secrets: i32) {
+        let entry = self.sets.entry(set.to_string()).or_default();
+        entry.0 |= completed;
+        entry.1 = entry.1.max(secrets);
+        Progress::save("progress", self);
+    }
+    fn meets(&self, unlock: &levels::Unlock) -> bool {
+        match unlock {
+            levels::Unlock::Complete(set) => self.sets.get(set).is_some_and(|s| s.0),
+            levels::Unlock::Secrets(n) => self.sets.values().map(|s| s.1).sum::<i32>() >= *n,
+        }
+    }
+    fn unlocked(&self, unlocks: &[levels::Unlock]) -> bool {
+        unlocks.iter().all(|u| self.meets(u))
+    }
+}
+
 const PAUSE_BG_FRAGMENT_SHADER: &'static str = include_str!("pause_bg.frag");
 const DEFAULT_VERTEX_SHADER: &'static str = "#version 100
 precision lowp float;
@@ -619,15 +762,114 @@ fn play_r_sound(
     }
 }
 
+// a room's own music wins over its theme's, and "none" means silence
+fn room_music(level: &levels::Level, themes: &[Theme]) -> Option<String> {
+    match level.meta.music.as_deref() {
+        Some("none") => None,
+        Some(p) => Some(p.to_string()),
+        None => themes[level.theme].mus.clone(),
+    }
+}
+
+fn play_room_music(
+    level: &levels::Level,
+    themes: &[Theme],
+    s: &mut HashMap<String, Playback>,
+    snds: &HashMap<String, RawSound>,
+    ctx: &AudioContext,
+) {
+    match room_music(level, themes) {
+        Some(p) => play_r_sound(s, snds, ctx, &p),
+        None => pause_everything(s, ctx),
+    }
+}
+
+fn load_room_music(
+    levelset: &levels::Levelset,
+    snds: &mut HashMap<String, RawSound>,
+    ctx: &AudioContext,
+) {
+    for l in levelset.levels.iter() {
+        match l.meta.music.as_deref() {
+            None | Some("none") => (),
+            Some(p) => {
+                if !snds.contains_key(p) {
+                    raw_sound!(snds, ctx, p);
+                }
+            }
+        }
+    }
+}
+
 fn pause_everything(s: &mut HashMap<String, Playback>, ctx: &AudioContext) {
     for (_, p) in s.iter_mut() {
         p.set_volume(ctx, 0.)
     }
 }
 
-#[macroquad::main(window_conf)]
-async fn main() {
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+
+    // notmarioland --convert levels/tutorial
+    if args.get(1).map(|a| a.as_str()) == Some("--convert") {
+        for path in args[2..].iter() {
+            let mut files = vec![];
+            if std::path::Path::new(path).is_dir() {
+                for entry in std::fs::read_dir(path).expect("should be a directory") {
+                    let p = entry.expect("should be readable").path();
+                    if p.extension().is_some_and(|e| e == "lvl") {
+                        files.push(p.to_string_lossy().to_string());
+                    }
+                }
+                files.sort();
+            } else {
+                files.push(path.clone());
+            }
+            for f in files {
+                if levels::convert_level(&f) {
+                    println!("converted {}", f);
+                } else {
+                    println!("{} is already v2", f);
+                }
+            }
+        }
+        return;
+    }
+
+    // notmarioland --import-tiled room.tmx [room.lvl]
+    if args.get(1).map(|a| a.as_str()) == Some("--import-tiled") {
+        let map_path = args.get(2).expect("should be given a map");
+        let out_path = match args.get(3) {
+            Some(p) => p.clone(),
+            None => std::path::Path::new(map_path)
+                .with_extension("lvl")
+                .to_string_lossy()
+                .to_string(),
+        };
+        std::fs::write(&out_path, tiled::to_lvl(map_path)).expect("should be able to write level");
+        println!("wrote {}", out_path);
+        return;
+    }
+
+    // notmarioland --pack levels/tutorial [tutorial.nmlpack]
+    if args.get(1).map(|a| a.as_str()) == Some("--pack") {
+        let dir = args.get(2).expect("should be given a levelset folder");
+        let dir = dir.trim_end_matches('/');
+        let out_path = match args.get(3) {
+            Some(p) => p.clone(),
+            None => format!("{}{}", dir, pack::PACK_EXT),
+        };
+        pack::write(dir, &out_path);
+        println!("wrote {}", out_path);
+        return;
+    }
+
+    macroquad::Window::from_config(window_conf(), game());
+}
+
+async fn game() {
     let mut settings = Settings::load("settings");
+    let mut progress = Progress::load("progress");
     settings.apply();
     let mut textures: HashMap<String, Texture2D> = HashMap::new();
     let mut sounds: HashMap<String, Sound> = HashMap::new();
@@ -696,7 +938,8 @@ async fn main() {
                     .to_str()
                     .expect("path should be string")
             );
-            if std::fs::read(t_path.clone()).is_err() {
+            // packs are read the same way as folders
+            if !pack::exists(&t_path) {
                 // println!("{}", t_path);
                 return None;
             }
@@ -705,14 +948,14 @@ async fn main() {
         })
         .collect();
 
+    let levelset_unlocks: HashMap<&String, Vec<levels::Unlock>> = levelsets
+        .iter()
+        .map(|l| (l, levels::load_unlocks(&format!("levels/{}", l))))
+        .collect();
+
     let levelset_has_image: HashMap<&String, bool> = levelsets
         .iter()
-        .map(|l| {
-            (
-                l,
-                std::fs::metadata(&format!("levels/{}/icon.png", l)).is_ok(),
-            )
-        })
+        .map(|l| (l, pack::exists(&format!("levels/{}/icon.png", l))))
         .collect();
 
     let preload_textures = [
@@ -738,12 +981,35 @@ async fn main() {
         "assets/slowsawlauncherright.png",
         "assets/slowsawlauncherup.png",
         "assets/slowsawlauncherdown.png",
+        "assets/bouncysaw.png",
+        "assets/bouncysawlauncherleft.png",
+        "assets/bouncysawlauncherright.png",
+        "assets/bouncysawlauncherup.png",
+        "assets/bouncysawlauncherdown.png",
+        "assets/turret.png",
+        "assets/turretcharge.png",
+        "assets/turretshot.png",
+        "assets/stalactite.png",
+        "assets/stalactiteshatter.png",
         "assets/secret.png",
+        "assets/challengesecret.png",
+        "assets/failgate.png",
+        "assets/coin.png",
+        "assets/extralife.png",
+        "assets/checkpoint.png",
+        "assets/breakable.png",
+        "assets/shockwave.png",
         "assets/goal.png",
+        "assets/sign.png",
         "assets/door.png",
         "assets/secretdoor.png",
         "assets/secretwindow.png",
         "assets/spike.png",
+        "assets/timedspikes.png",
+        "assets/spikeup.png",
+        "assets/spikedown.png",
+        "assets/spikeleft.png",
+        "assets/spikeright.png",
         "assets/jumparrow.png",
         "assets/jumparrowoutline.png",
         "assets/jumparrowfill.png",
@@ -826,6 +1092,8 @@ async fn main() {
     let mut paused = false;
     let mut paused_frames = 0;
     let mut paused_selection = 0;
+    let mut showing_map = false;
+    let mut map_scroll = (0, 0);
 
     let mut keys_pressed: HashMap<KeyCode, bool> = HashMap::new();
 
@@ -834,10 +1102,21 @@ async fn main() {
     let mut themes = vec![];
     let mut deaths = 0;
     let mut secret_count = 0;
+    let mut coin_count = 0;
     let mut transition_ticks: i32 = 0;
     let mut secret_transition = false;
     let mut next_ind: Option<usize> = None;
+    let mut respawn_at_checkpoint = false;
+    // why the last door we tried wouldn't open, and for how many more frames to show it
+    let mut door_message: Option<(String, i32)> = None;
     let mut levelset_ind = 0;
+    // levelset being played, and the ones left behind through doors this session
+    let mut current_set = String::new();
+    let mut set_stash: HashMap<String, SetProgress> = HashMap::new();
+    // levelsets entered through a door, to the levelset, room and door they were entered from.
+    // beating one of those goes back out that door instead of ending the game
+    let mut entered_from: HashMap<String, (String, String, usize)> = HashMap::new();
+    let mut leaving_set = false;
 
     let mut global_timer: f32 = 0.;
 
@@ -967,6 +1246,7 @@ async fn main() {
                         );
 
                         let base_levelsets = ["tutorial".to_string(), "doublejump".to_string()];
+                        let mut selected_locked = false;
 
                         for (i, l) in base_levelsets
                             .iter()
@@ -1002,12 +1282,41 @@ async fn main() {
                             } else {
                                 draw_text_cool_c(
                                     &font,
-                                    &l,
+                                    l.trim_end_matches(pack::PACK_EXT),
                                     SCREEN_WIDTH / 2 - 160 * (*ind as i32 - i as i32),
                                     128 + 16 * (i as i32 - *ind as i32) + off_y + 40,
                                     WHITE,
                                 );
                             }
+                            if !progress.unlocked(&levelset_unlocks[l]) {
+                                let x = SCREEN_WIDTH / 2 - 64 - 160 * (*ind as i32 - i as i32);
+                                let y = 128 + 16 * (i as i32 - *ind as i32) + off_y;
+                                draw_rectangle(
+                                    x as f32,
+                                    y as f32,
+                                    128.,
+                                    96.,
+                                    color_u8!(0, 0, 0, 160),
+                                );
+                                let t = texture!(&mut textures, "assets/levellocked.png");
+                                draw_texture(&t, (x + 56) as f32, (y + 40) as f32, WHITE);
+
+                                if i == *ind {
+                                    selected_locked = true;
+                                    let unmet: Vec<String> = levelset_unlocks[l]
+                                        .iter()
+                                        .filter(|u| !progress.meets(u))
+                                        .map(|u| u.description())
+                                        .collect();
+                                    draw_text_cool_c(
+                                        &font,
+                                        &unmet.join(", "),
+                                        SCREEN_WIDTH / 2,
+                                        y + 116,
+                                        WHITE,
+                                    );
+                                }
+                            }
                         }
                         let t = texture!(&mut textures, "assets/levelselector.png");
                         draw_texture(
@@ -1026,6 +1335,11 @@ async fn main() {
                         );
 
                         let m = |b| if b { "yes" } else { "no" };
+                        let lives = if mods.lives == 0 {
+                            "off".to_string()
+                        } else {
+                            format!("{}", mods.lives)
+                        };
 
                         let modifier_menu = [
                             ("levels", "", ""),
@@ -1061,6 +1375,8 @@ async fn main() {
                                 m(mods.infinitejumps),
                             ),
                             ("unkillable", "assets/unkillable.png", m(mods.unkillable)),
+                            ("lives", "assets/extralife.png", &lives),
+                            ("hardcore", "assets/hardcore.png", m(mods.hardcore)),
                         ];
 
                         for (i, (name, img, val)) in modifier_menu.iter().enumerate() {
@@ -1096,6 +1412,16 @@ async fn main() {
                                 "uncapped speed" => mods.uncapped_speed = !mods.uncapped_speed,
                                 "infinite jumps" => mods.infinitejumps = !mods.infinitejumps,
                                 "unkillable" => mods.unkillable = !mods.unkillable,
+                                "lives" => {
+                                    mods.lives = match mods.lives {
+                                        0 => 1,
+                                        1 => 3,
+                                        3 => 5,
+                                        5 => 9,
+                                        _ => 0,
+                                    }
+                                }
+                                "hardcore" => mods.hardcore = !mods.hardcore,
                                 _ => (),
                             }
                         }
@@ -1109,7 +1435,7 @@ async fn main() {
                                 *ind -= 1
                             }
 
-                            if is_key_pressed(KeyCode::Z) {
+                            if is_key_pressed(KeyCode::Z) && !selected_locked {
                                 if *ind == levelsets.len() {
                                     *menu_state = MenuState::Main(0);
                                 } else {
@@ -1127,6 +1453,9 @@ async fn main() {
                                         .iter()
                                         .position(|k| k == l)
                                         .expect("should be findable");
+                                    current_set = l.trim_end_matches(pack::PACK_EXT).to_string();
+                                    set_stash.clear();
+                                    entered_from.clear();
                                     let current_ind = 0; // we assume the first level is index 0
 
                                     let level_raw = levelset.levels[current_ind].clone();
@@ -1144,6 +1473,7 @@ async fn main() {
                                     themes = levelset.themes.clone();
                                     deaths = 0;
                                     secret_count = levelset.secret_count;
+                                    coin_count = levelset.coin_count;
                                     transition_ticks = 0;
                                     secret_transition = false;
                                     paused_frames = 0;
@@ -1162,25 +1492,28 @@ async fn main() {
                                             raw_sound!(rawsounds, &music_ctx, p);
                                         }
                                     }
+                                    load_room_music(&levelset, &mut rawsounds, &music_ctx);
 
                                     clear_playbacks(&music_ctx, &mut current_musics);
 
                                     // play new music
-                                    match &themes[level.theme].mus {
-                                        Some(p) => play_r_sound(
-                                            &mut current_musics,
-                                            &rawsounds,
-                                            &music_ctx,
-                                            p,
-                                        ),
-                                        None => pause_everything(&mut current_musics, &music_ctx),
-                                    }
+                                    play_room_music(
+                                        &level,
+                                        &themes,
+                                        &mut current_musics,
+                                        &rawsounds,
+                                        &music_ctx,
+                                    );
+
+                                    let mut global_state =
+                                        levels::GlobalState::new(Some(*mods), levelset.physics);
+                                    global_state.enter_room(&level, &themes[level.theme]);
 
                                     state = State::Game {
                                         levelset: Some(levelset),
                                         current_ind,
                                         level,
-                                        global_state: levels::GlobalState::new(Some(*mods)),
+                                        global_state,
                                         won: false,
                                     }
                                 }
@@ -1269,6 +1602,10 @@ async fn main() {
                 if is_key_pressed(KeyCode::Escape) {
                     if global_state.binocularing {
                         global_state.binocularing = false;
+                    } else if global_state.reading.is_some() {
+                        global_state.reading = None;
+                    } else if showing_map {
+                        showing_map = false;
                     } else {
                         paused = !paused;
                         paused_selection = 0;
@@ -1282,12 +1619,13 @@ async fn main() {
                             )
                         } else {
                             // play new music
-                            match &themes[level.theme].mus {
-                                Some(p) => {
-                                    play_r_sound(&mut current_musics, &rawsounds, &music_ctx, p)
-                                }
-                                None => pause_everything(&mut current_musics, &music_ctx),
-                            }
+                            play_room_music(
+                                level,
+                                &themes,
+                                &mut current_musics,
+                                &rawsounds,
+                                &music_ctx,
+                            );
                         }
                     }
                 }
@@ -1336,13 +1674,116 @@ async fn main() {
                         }
                     }
 
+                    if transition_ticks == -1
+                        && remaining_timer * 60. >= 1.
+                        && global_state.game_over
+                    {
+                        *won = true;
+                        clear_input_queue();
+                        paused_selection = 0;
+                        play_r_sound(
+                            &mut current_musics,
+                            &rawsounds,
+                            &music_ctx,
+                            &"assets/mus/pause.ogg".into(),
+                        )
+                    }
+
                     if transition_ticks == -1 && remaining_timer * 60. >= 1. {
-                        if let Some(index) = next_ind {
+                        if let Some(mut index) = next_ind {
+                            let old_mus = room_music(level, &themes);
+                            // the door to come out of in the new room
+                            let mut arrival_door = Some(*current_ind);
+
+                            // doors can lead into another levelset. the one we leave is put away
+                            // as it is, so coming back later picks up where the player left off
+                            let back = if leaving_set {
+                                entered_from.remove(&current_set)
+                            } else {
+                                None
+                            };
+                            let foreign = match &back {
+                                Some((set, room, _)) => Some((set.clone(), room.clone())),
+                                None => levelset
+                                    .as_ref()
+                                    .expect("is some")
+                                    .foreign_room(index)
+                                    .cloned(),
+                            };
+                            if let Some((set, room)) = foreign {
+                                let old_set = levelset.take().expect("is some");
+                                let old_room = old_set.room_names[*current_ind].clone();
+                                let progress = match set_stash.remove(&set) {
+                                    Some(p) => p,
+                                    None => {
+                                        let new_set = levels::load_levelset(&levelset_path(&set));
+                                        SetProgress {
+                                            global_state: levels::GlobalState::new(
+                                                Some(global_state.default_modifiers),
+                                                new_set.physics,
+                                            ),
+                                            levelset: new_set,
+                                            deaths: 0,
+                                        }
+                                    }
+                                };
+
+                                let mut new_state = progress.global_state;
+                                new_state.carry_over(global_state);
+                                let old_state = std::mem::replace(global_state, new_state);
+                                // a beaten levelset starts over if it's entered again
+                                if !leaving_set {
+                                    set_stash.insert(
+                                        current_set.clone(),
+                                        SetProgress {
+                                            levelset: old_set,
+                                            global_state: old_state,
+                                            deaths,
+                                        },
+                                    );
+                                }
+                                deaths = progress.deaths;
+
+                                let new_set = progress.levelset;
+                                arrival_door = match back {
+                                    Some((_, _, door)) => Some(door),
+                                    None => {
+                                        entered_from.insert(
+                                            set.clone(),
+                                            (current_set.clone(), old_room.clone(), index),
+                                        );
+                                        new_set.door_to(&current_set, &old_room)
+                                    }
+                                };
+                                index = new_set.room_index(&room);
+                                leaving_set = false;
+                                secret_count = new_set.secret_count;
+                                coin_count = new_set.coin_count;
+                                themes = new_set.themes.clone();
+                                if themes.is_empty() {
+                                    themes.push(Theme::default())
+                                }
+                                for t in themes.iter() {
+                                    t.load_textures(&mut textures).await;
+                                    if let Some(p) = t.mus.clone() {
+                                        if !rawsounds.contains_key(&p) {
+                                            raw_sound!(rawsounds, &music_ctx, p);
+                                        }
+                                    }
+                                }
+                                load_room_music(&new_set, &mut rawsounds, &music_ctx);
+                                *levelset = Some(new_set);
+                                // restarting from the pause menu starts over in the set we're in
+                                levelset_ind = levelsets
+                                    .iter()
+                                    .position(|l| l.trim_end_matches(pack::PACK_EXT) == set)
+                                    .expect("levelset should be in the level select");
+                                current_set = set;
+                            }
+
                             let level_raw =
                                 levelset.as_ref().expect("is some").levels[index].clone();
 
-                            let old_ind = *current_ind;
-                            let old_theme = &themes[level.theme];
                             *current_ind = index;
                             *level = levels::Level::from_level_raw(
                                 level_raw,
@@ -1351,17 +1792,23 @@ async fn main() {
                                 &global_state.changed_tiles,
                             );
 
-                            if themes[level.theme].mus != old_theme.mus {
+                            if room_music(level, &themes) != old_mus {
                                 // play new music
-                                match &themes[level.theme].mus {
-                                    Some(p) => {
-                                        play_r_sound(&mut current_musics, &rawsounds, &music_ctx, p)
-                                    }
-                                    None => pause_everything(&mut current_musics, &music_ctx),
-                                }
+                                play_room_music(
+                                    level,
+                                    &themes,
+                                    &mut current_musics,
+                                    &rawsounds,
+                                    &music_ctx,
+                                );
                             }
 
-                            let p_pos = levels::find_door(old_ind, &level.tiles);
+                            let p_pos = if respawn_at_checkpoint {
+                                levels::find_checkpoint(&level.tiles)
+                            } else {
+                                arrival_door.and_then(|d| levels::find_door(d, &level.tiles))
+                            };
+                            respawn_at_checkpoint = false;
                             println!("{:?}", p_pos);
                             if let Some((x, y)) = p_pos {
                                 let p_obj = level.player_obj();
@@ -1386,52 +1833,93 @@ async fn main() {
                             );
                         }
 
-                        global_state.jumps = 0;
-                        global_state.collected_jump_arrows = std::collections::VecDeque::new();
-                        global_state.modifiers = global_state.default_modifiers;
+                        global_state.enter_room(level, &themes[level.theme]);
                     }
 
                     if remaining_timer * 60. >= 1. && transition_ticks >= 0 {
-                        level.update(&mut keys_pressed, global_state, &mut sounds);
+                        // jump turns the page, and closes the sign after the last one
+                        if let Some((sign, page)) = global_state.reading {
+                            if *keys_pressed.entry(KeyCode::Z).or_insert(false) {
+                                keys_pressed.insert(KeyCode::Z, false);
+                                global_state.reading = if page + 1 < level.signs[sign].len() {
+                                    Some((sign, page + 1))
+                                } else {
+                                    None
+                                };
+                            }
+                        }
+
+                        // the whole room holds still while a sign is open
+                        let reading = global_state.reading.is_some();
+                        if !reading {
+                            level.update(&mut keys_pressed, global_state, &mut sounds);
+                        }
+                        if !global_state.visited.contains(current_ind) {
+                            global_state.visited.push(*current_ind);
+                        }
+                        let out_of_time = !reading && level.tick_challenge(global_state);
 
                         let pbb = level.player_obj().get_aabb();
 
+                        let old_secrets = global_state.secrets;
                         levels::collect_keys(pbb, *current_ind, &mut level.tiles, global_state);
                         levels::collect_doors(pbb, *current_ind, &mut level.tiles, global_state);
+                        if global_state.secrets > old_secrets {
+                            progress.record(&current_set, false, global_state.secrets);
+                        }
+
+                        if level.player_obj().pound_landed {
+                            level.player_obj().pound_landed = false;
+                            if levels::break_tiles_below(
+                                pbb,
+                                *current_ind,
+                                &mut level.tiles,
+                                global_state,
+                            ) {
+                                // keep slamming through
+                                level.player_obj().pounding = true;
+                            }
+                        }
 
                         // check if we should exit!!
                         let player_pos = level.focus_position();
                         let player_vel = level.player_vel();
                         let d = level.dimensions();
                         if levelset.is_some() && player_pos.0 < 0 && player_vel.0 < 0 {
-                            if level.side_exits.left.is_some() {
+                            let exit = level.exit_towards(
+                                levels::Direction::Left,
+                                player_pos.1 - TILE_SIZE / 2,
+                                &levelset.as_ref().expect("is some").levels,
+                                *current_ind,
+                            );
+                            if let Some((index, r_off_y, new_off_y)) = exit {
                                 let old_sliding = level.player_obj().wall_sliding;
                                 let old_freeze = level.player_obj().freeze_timer;
 
-                                let r_off_y =
-                                    level.side_offsets.left.expect("should have an exit anchor");
                                 let off_y = player_pos.1 - TILE_SIZE / 2 - r_off_y;
                                 let new_x = player_pos.0 - TILE_SIZE / 2;
 
-                                let level_raw = levelset.as_ref().expect("is some").levels
-                                    [level.side_exits.left.expect("is some")]
-                                .clone();
+                                let level_raw =
+                                    levelset.as_ref().expect("is some").levels[index].clone();
 
-                                *current_ind = level.side_exits.left.expect("is some");
+                                let old_mus = room_music(level, &themes);
+                                *current_ind = index;
                                 *level = levels::Level::from_level_raw(
                                     level_raw,
                                     *current_ind,
                                     &levelset.as_ref().unwrap().levels,
                                     &global_state.changed_tiles,
                                 );
-                                global_state.jumps = 0;
-                                global_state.collected_jump_arrows =
-                                    std::collections::VecDeque::new();
-                                global_state.modifiers = global_state.default_modifiers;
-                                let new_off_y = level
-                                    .side_offsets
-                                    .right
-                                    .expect("should have an exit anchor");
+                                global_state.enter_room(level, &themes[level.theme]);
+                                if room_music(level, &themes) != old_mus {
+                                    play_room_music(
+                                        level,
+                                        &themes,
+                                        &mut current_musics,
+                                        &rawsounds,
+                                        &music_ctx,
+                                    );
+                                }
                                 let new_off_x = level.dimensions().0 * TILE_SIZE;
 
                                 render_off_x -= (level.dimensions().0 * TILE_PIXELS) as f32;
@@ -1452,35 +1940,40 @@ async fn main() {
                             && player_pos.0 > d.0 * TILE_SIZE
                             && player_vel.0 > 0
                         {
-                            if level.side_exits.right.is_some() {
+                            let exit = level.exit_towards(
+                                levels::Direction::Right,
+                                player_pos.1 - TILE_SIZE / 2,
+                                &levelset.as_ref().expect("is some").levels,
+                                *current_ind,
+                            );
+                            if let Some((index, r_off_y, new_off_y)) = exit {
                                 let old_sliding = level.player_obj().wall_sliding;
                                 let old_freeze = level.player_obj().freeze_timer;
 
-                                let r_off_y = level
-                                    .side_offsets
-                                    .right
-                                    .expect("should have an exit anchor");
                                 let off_y = player_pos.1 - TILE_SIZE / 2 - r_off_y;
                                 let new_x = player_pos.0 - d.0 * TILE_SIZE - TILE_SIZE / 2;
 
-                                let level_raw = levelset.as_ref().expect("is some").levels
-                                    [level.side_exits.right.expect("is some")]
-                                .clone();
+                                let level_raw =
+                                    levelset.as_ref().expect("is some").levels[index].clone();
 
-                                *current_ind = level.side_exits.right.expect("is some");
+                                let old_mus = room_music(level, &themes);
+                                *current_ind = index;
                                 *level = levels::Level::from_level_raw(
                                     level_raw,
                                     *current_ind,
                                     &levelset.as_ref().unwrap().levels,
                                     &global_state.changed_tiles,
                                 );
-                                global_state.jumps = 0;
-                                global_state.collected_jump_arrows =
-                                    std::collections::VecDeque::new();
-                                global_state.modifiers = global_state.default_modifiers;
-                                let new_off_y =
-                                    level.side_offsets.left.expect("should have an exit anchor");
-
+                                global_state.enter_room(level, &themes[level.theme]);
+                                if room_music(level, &themes) != old_mus {
+                                    play_room_music(
+                                        level,
+                                        &themes,
+                                        &mut current_musics,
+                                        &rawsounds,
+                                        &music_ctx,
+                                    );
+                                }
                                 render_off_x += (d.0 * TILE_PIXELS) as f32;
                                 render_off_y -= ((new_off_y - r_off_y) / PIXEL_SIZE) as f32;
 
@@ -1495,32 +1988,40 @@ async fn main() {
                                 p.x = d.0 * TILE_SIZE - TILE_SIZE / 2;
                             }
                         } else if levelset.is_some() && player_pos.1 < 0 && player_vel.1 < 0 {
-                            if level.side_exits.up.is_some() {
+                            let exit = level.exit_towards(
+                                levels::Direction::Up,
+                                player_pos.0 - TILE_SIZE / 2,
+                                &levelset.as_ref().expect("is some").levels,
+                                *current_ind,
+                            );
+                            if let Some((index, r_off_x, new_off_x)) = exit {
                                 let old_sliding = level.player_obj().wall_sliding;
                                 let old_freeze = level.player_obj().freeze_timer;
 
-                                let r_off_x =
-                                    level.side_offsets.up.expect("should have an exit anchor");
                                 let off_x = player_pos.0 - TILE_SIZE / 2 - r_off_x;
                                 let new_y = player_pos.1 - TILE_SIZE / 2;
 
-                                let level_raw = levelset.as_ref().expect("is some").levels
-                                    [level.side_exits.up.expect("is some")]
-                                .clone();
+                                let level_raw =
+                                    levelset.as_ref().expect("is some").levels[index].clone();
 
-                                *current_ind = level.side_exits.up.expect("is some");
+                                let old_mus = room_music(level, &themes);
+                                *current_ind = index;
                                 *level = levels::Level::from_level_raw(
                                     level_raw,
                                     *current_ind,
                                     &levelset.as_ref().unwrap().levels,
                                     &global_state.changed_tiles,
                                 );
-                                global_state.jumps = 0;
-                                global_state.collected_jump_arrows =
-                                    std::collections::VecDeque::new();
-                                global_state.modifiers = global_state.default_modifiers;
-                                let new_off_x =
-                                    level.side_offsets.down.expect("should have an exit anchor");
+                                global_state.enter_room(level, &themes[level.theme]);
+                                if room_music(level, &themes) != old_mus {
+                                    play_room_music(
+                                        level,
+                                        &themes,
+                                        &mut current_musics,
+                                        &rawsounds,
+                                        &music_ctx,
+                                    );
+                                }
                                 let new_off_y = level.dimensions().1 * TILE_SIZE;
 
                                 render_off_y -= (level.dimensions().1 * TILE_PIXELS) as f32;
@@ -1538,34 +2039,41 @@ async fn main() {
                             && player_pos.1 > d.1 * TILE_SIZE
                             && player_vel.1 > 0
                         {
-                            if level.side_exits.down.is_some() {
+                            let exit = level.exit_towards(
+                                levels::Direction::Down,
+                                player_pos.0 - TILE_SIZE / 2,
+                                &levelset.as_ref().expect("is some").levels,
+                                *current_ind,
+                            );
+                            if let Some((index, r_off_x, new_off_x)) = exit {
                                 let old_sliding = level.player_obj().wall_sliding;
                                 let old_freeze = level.player_obj().freeze_timer;
 
-                                let r_off_x =
-                                    level.side_offsets.down.expect("should have an exit anchor");
                                 let off_x = player_pos.0 - TILE_SIZE / 2 - r_off_x;
                                 let new_y =
                                     player_pos.1 - TILE_SIZE / 2 - level.dimensions().1 * TILE_SIZE;
 
-                                let level_raw = levelset.as_ref().expect("is some").levels
-                                    [level.side_exits.down.expect("is some")]
-                                .clone();
+                                let level_raw =
+                                    levelset.as_ref().expect("is some").levels[index].clone();
 
-                                *current_ind = level.side_exits.down.expect("is some");
+                                let old_mus = room_music(level, &themes);
+                                *current_ind = index;
                                 *level = levels::Level::from_level_raw(
                                     level_raw,
                                     *current_ind,
                                     &levelset.as_ref().unwrap().levels,
                                     &global_state.changed_tiles,
                                 );
-                                global_state.jumps = 0;
-                                global_state.collected_jump_arrows =
-                                    std::collections::VecDeque::new();
-                                global_state.modifiers = global_state.default_modifiers;
-                                let new_off_x =
-                                    level.side_offsets.up.expect("should have an exit anchor");
-
+                                global_state.enter_room(level, &themes[level.theme]);
+                                if room_music(level, &themes) != old_mus {
+                                    play_room_music(
+                                        level,
+                                        &themes,
+                                        &mut current_musics,
+                                        &rawsounds,
+                                        &music_ctx,
+                                    );
+                                }
                                 render_off_y += (d.1 * TILE_PIXELS) as f32;
                                 render_off_x -= ((new_off_x - r_off_x) / PIXEL_SIZE) as f32;
 
@@ -1585,6 +2093,10 @@ async fn main() {
                                     secret_transition = false;
 
                                     deaths += 1;
+                                    if let Some(index) = global_state.lose_life() {
+                                        next_ind = Some(index);
+                                        respawn_at_checkpoint = true;
+                                    }
 
                                     let s = sound!(&mut sounds, "assets/mus/death.ogg");
                                     play_sound(
@@ -1601,15 +2113,39 @@ async fn main() {
                         } else if levelset.is_some()
                             && *keys_pressed.entry(KeyCode::Up).or_insert(false)
                             && !global_state.binocularing
+                            && global_state.reading.is_none()
                         {
                             let p_obj = level.player_obj();
                             let grounded = p_obj.grounded;
                             let aabb = (p_obj as &mut dyn Object).get_aabb();
 
                             let doors = levels::check_door(aabb, &level.tiles);
+                            // doors into a levelset that isn't unlocked yet stay shut,
+                            // and say what's still missing
+                            let mut door_locked = |index: usize| {
+                                let Some((set, _)) =
+                                    levelset.as_ref().expect("is some").foreign_room(index)
+                                else {
+                                    return false;
+                                };
+                                let unmet: Vec<String> = levelset_unlocks
+                                    .iter()
+                                    .find(|(l, _)| l.trim_end_matches(pack::PACK_EXT) == set)
+                                    .map(|(_, unlocks)| unlocks.as_slice())
+                                    .unwrap_or_default()
+                                    .iter()
+                                    .filter(|u| !progress.meets(u))
+                                    .map(|u| u.description())
+                                    .collect();
+                                if unmet.is_empty() {
+                                    return false;
+                                }
+                                door_message = Some((unmet.join(", "), 120));
+                                true
+                            };
 
                             if let Some(levels::Tile::Door(index)) = doors {
-                                if grounded {
+                                if grounded && !door_locked(index) {
                                     // println!("we should be going to {}", index);
 
                                     transition_ticks = -20;
@@ -1617,13 +2153,17 @@ async fn main() {
                                     next_ind = Some(index)
                                 }
                             } else if let Some(levels::Tile::SecretDoor(index)) = doors {
-                                if grounded {
+                                if grounded && !door_locked(index) {
                                     // println!("we should be going to {}", index);
 
                                     transition_ticks = -20;
                                     secret_transition = true;
                                     next_ind = Some(index)
                                 }
+                            } else if let Some(levels::Tile::Sign(index)) = doors {
+                                if grounded {
+                                    global_state.reading = Some((index, 0));
+                                }
                             } else if let Some(levels::Tile::Binocular) = doors {
                                 if grounded {
                                     global_state.binocularing = true;
@@ -1665,8 +2205,9 @@ async fn main() {
                             let p_obj = level.player_obj();
                             let aabb = (p_obj as &mut dyn Object).get_aabb();
 
-                            if (levels::check_tilemap_death(aabb, &level.tiles)
-                                || levels::check_object_death(aabb, &level.objects))
+                            if (levels::check_tilemap_death(aabb, &level.tiles, global_state)
+                                || levels::check_object_death(aabb, &level.objects)
+                                || out_of_time)
                                 && !global_state.modifiers.unkillable
                             {
                                 if levelset.is_some() {
@@ -1675,6 +2216,10 @@ async fn main() {
                                     secret_transition = false;
 
                                     deaths += 1;
+                                    if let Some(index) = global_state.lose_life() {
+                                        next_ind = Some(index);
+                                        respawn_at_checkpoint = true;
+                                    }
 
                                     let s = sound!(&mut sounds, "assets/mus/death.ogg");
                                     play_sound(
@@ -1689,7 +2234,16 @@ async fn main() {
                                 }
                             }
 
-                            if levels::check_tilemap_win(aabb, &level.tiles) {
+                            if levels::check_tilemap_win(aabb, &level.tiles)
+                                && entered_from.contains_key(&current_set)
+                            {
+                                progress.record(&current_set, true, global_state.secrets);
+                                transition_ticks = -20;
+                                secret_transition = false;
+                                leaving_set = true;
+                                next_ind = Some(*current_ind);
+                            } else if levels::check_tilemap_win(aabb, &level.tiles) {
+                                progress.record(&current_set, true, global_state.secrets);
                                 *won = true;
                                 clear_input_queue();
                                 paused_selection = 0;
@@ -1776,7 +2330,27 @@ async fn main() {
                         paused_frames += 1;
                         remaining_timer -= 1. / 60.;
                     }
-                    if !*won {
+                    if showing_map {
+                        if is_key_pressed(KeyCode::X) {
+                            showing_map = false;
+                        }
+                        if is_key_down(KeyCode::Left) {
+                            map_scroll.0 += 4;
+                        }
+                        if is_key_down(KeyCode::Right) {
+                            map_scroll.0 -= 4;
+                        }
+                        if is_key_down(KeyCode::Up) {
+                            map_scroll.1 += 4;
+                        }
+                        if is_key_down(KeyCode::Down) {
+                            map_scroll.1 -= 4;
+                        }
+                    } else if !*won {
+                        if is_key_pressed(KeyCode::X) && levelset.is_some() {
+                            showing_map = true;
+                            map_scroll = (0, 0);
+                        }
                         if is_key_pressed(KeyCode::Down) && paused_selection < 2 {
                             paused_selection += 1;
                         }
@@ -1987,6 +2561,28 @@ async fn main() {
                     WHITE,
                 );
 
+                if let Some((msg, ticks)) = &mut door_message {
+                    draw_rectangle(
+                        0.,
+                        SCREEN_HEIGHT as f32 - 36.,
+                        SCREEN_WIDTH as f32,
+                        18.,
+                        color_u8!(0, 0, 0, 191),
+                    );
+                    draw_text_cool_c(&font, msg, SCREEN_WIDTH / 2, SCREEN_HEIGHT - 35, WHITE);
+                    *ticks -= 1;
+                    if *ticks <= 0 {
+                        door_message = None;
+                    }
+                }
+
+                // icons for whatever this room forces on, right after its name
+                for (i, p) in level.modifiers.iter().enumerate() {
+                    let t = texture_cache!(&mut textures, p.image());
+                    let x = SCREEN_WIDTH / 2 + text_width(&level.name) / 2 + 4 + 16 * i as i32;
+                    draw_texture(&t, x as f32, (SCREEN_HEIGHT - 17) as f32, WHITE);
+                }
+
                 if settings.show_stats {
                     let vel = level.player_vel();
                     // let g = level.player_obj().air_frames;
@@ -2006,10 +2602,17 @@ async fn main() {
                     );
 
                     // this will break when stuff happens
+                    // coins get a $ so they can't be mistaken for the secrets next to them
+                    let coins = if coin_count > 0 {
+                        format!("|${}/{}", global_state.coins, coin_count)
+                    } else {
+                        String::new()
+                    };
                     let t = format!(
-                        "{}/{}|{:0>2}:{:0>2}|Æ{}",
+                        "{}/{}{}|{:0>2}:{:0>2}|Æ{}",
                         global_state.secrets,
                         secret_count,
+                        coins,
                         global_state.timer / 3600,
                         (global_state.timer / 60) % 60,
                         deaths,
@@ -2017,11 +2620,93 @@ async fn main() {
                     draw_text_cool_l(&font, &t, SCREEN_WIDTH - 2, SCREEN_HEIGHT - 17, WHITE);
                 }
 
+                // coins, lives and keys stack down from the top left corner
+                let mut hud_y = 2;
+
+                if coin_count > 0 {
+                    let room_coins =
+                        levelset.as_ref().expect("is some").levels[*current_ind].coin_count();
+                    let t = texture_cache!(&mut textures, "assets/coin.png");
+                    draw_texture(&t, 2., hud_y as f32, WHITE);
+                    let t = format!(
+                        "{}/{}|{}/{}",
+                        room_coins - level.coin_count(),
+                        room_coins,
+                        global_state.coins,
+                        coin_count,
+                    );
+                    draw_text_cool(&font, &t, 20, hud_y, WHITE);
+                    hud_y += 18;
+                }
+
+                if let Some((sign, page)) = global_state.reading {
+                    draw_rectangle(
+                        (SCREEN_WIDTH / 2 - 180) as f32,
+                        (SCREEN_HEIGHT - 104) as f32,
+                        360.,
+                        76.,
+                        color_u8!(0, 0, 0, 191),
+                    );
+                    draw_tip_text(
+                        &font,
+                        &level.signs[sign][page],
+                        SCREEN_WIDTH / 2 - 168,
+                        SCREEN_HEIGHT - 70,
+                        336,
+                        999,
+                        WHITE,
+                    );
+                    if page + 1 < level.signs[sign].len() {
+                        draw_text_cool_l(
+                            &font,
+                            ">",
+                            SCREEN_WIDTH / 2 + 176,
+                            SCREEN_HEIGHT - 46,
+                            WHITE,
+                        );
+                    }
+                }
+
+                if let Some(left) = global_state.challenge_timer {
+                    let numbers = texture_cache!(&mut textures, "assets/numbers.png");
+                    let t = format!("{:0>2}:{:0>2}", left / 60, left % 60 * 100 / 60);
+                    draw_number_text(
+                        &numbers,
+                        &t,
+                        (SCREEN_WIDTH / 2 - 48) as f32,
+                        4.,
+                        if left < 180 {
+                            color_u8!(255, 104, 104, 255)
+                        } else {
+                            WHITE
+                        },
+                        global_state.timer,
+                    );
+                }
+
+                if global_state.default_modifiers.lives > 0 {
+                    let t = texture_cache!(&mut textures, "assets/extralife.png");
+                    draw_texture(&t, 2., hud_y as f32, WHITE);
+                    let t = format!("{}", global_state.lives);
+                    draw_text_cool(&font, &t, 20, hud_y, WHITE);
+                    hud_y += 18;
+                }
+
                 let imgs = global_state.modifiers.get_images();
                 for (i, img) in imgs.iter().enumerate() {
                     let t = texture_cache!(&mut textures, *img);
                     let x = SCREEN_WIDTH - 16 * (imgs.len() - i) as i32;
                     draw_texture(&t, x as f32, 0., WHITE);
+
+                    // timed pickups get a bar that runs out
+                    if let Some((_, left, total)) = global_state
+                        .modifier_timers
+                        .iter()
+                        .find(|(p, _, _)| p.image() == *img)
+                    {
+                        draw_rectangle(x as f32, 16., 16., 2., color_u8!(0, 0, 0, 191));
+                        draw_rectangle(x as f32, 16., (16 * left / total) as f32, 2., WHITE);
+                    }
                 }
 
                 if settings.show_input {
@@ -2055,7 +2740,7 @@ async fn main() {
                     }
                 }
 
-                let mut key_pos = 2.;
+                let mut key_pos = hud_y as f32;
                 for (count, colour) in global_state
                     .keys
                     .iter()
@@ -2195,7 +2880,7 @@ async fn main() {
                             &numbers,
                             &t,
                             64. + (-200. * (1. - prog)) as i32 as f32,
-                            149.,
+                            113.,
                             BLACK,
                             global_state.timer,
                         );
@@ -2206,7 +2891,7 @@ async fn main() {
                             &numbers,
                             &t,
                             65. + (-200. * (1. - prog)) as i32 as f32,
-                            186.,
+                            150.,
                             color_u8!(79, 6, 79, 255),
                             global_state.timer,
                         );
@@ -2216,10 +2901,20 @@ async fn main() {
                             &numbers,
                             &t,
                             67. + (-200. * (1. - prog)) as i32 as f32,
-                            221.,
+                            185.,
                             color_u8!(79, 6, 6, 255),
                             global_state.timer,
                         );
+                        let t = format!("{}/{}", global_state.coins, coin_count);
+
+                        draw_number_text(
+                            &numbers,
+                            &t,
+                            67. + (-200. * (1. - prog)) as i32 as f32,
+                            220.,
+                            color_u8!(111, 79, 6, 255),
+                            global_state.timer,
+                        );
 
                         let t = texture_cache!(textures, "assets/pausebottom.png");
                         draw_texture(&t, 0., (150. * (1. - prog)) as i32 as f32, WHITE);
@@ -2255,6 +2950,121 @@ async fn main() {
                             }
                         );
                         draw_texture(&t, (320. * (1. - prog)) as i32 as f32, 0., WHITE);
+
+                        draw_text_cool_l(
+                            &font,
+                            "x - map",
+                            SCREEN_WIDTH - 4 + (320. * (1. - prog)) as i32,
+                            SCREEN_HEIGHT - 20,
+                            WHITE,
+                        );
+
+                        if showing_map {
+                            draw_rectangle(
+                                0.,
+                                0.,
+                                SCREEN_WIDTH as f32,
+                                SCREEN_HEIGHT as f32,
+                                color_u8!(0, 0, 0, 224),
+                            );
+
+                            let levels = &levelset.as_ref().expect("is some").levels;
+                            let (lx, ly) = (
+                                levels[*current_ind].tiles[0][0].len() as i32,
+                                levels[*current_ind].tiles[0].len() as i32,
+                            );
+                            let (ox, oy) = (
+                                SCREEN_WIDTH / 2 - lx + map_scroll.0,
+                                SCREEN_HEIGHT / 2 - ly + map_scroll.1,
+                            );
+                            let mut seen = vec![*current_ind];
+                            let mut area = levels[*current_ind].minimap_draw(
+                                ox,
+                                oy,
+                                levels,
+                                &mut seen,
+                                *current_ind,
+                                &global_state.changed_tiles,
+                                &global_state.visited,
+                                false,
+                            );
+
+                            // rooms only reachable through doors go off to the right
+                            for ind in global_state.visited.iter() {
+                                if seen.contains(ind) {
+                                    continue;
+                                }
+                                seen.push(*ind);
+                                let (x1, y1, _, _) = levels[*ind].minimap_draw(
+                                    0,
+                                    0,
+                                    levels,
+                                    &mut seen.clone(),
+                                    *ind,
+                                    &global_state.changed_tiles,
+                                    &global_state.visited,
+                                    true,
+                                );
+                                let (_, _, x2, y2) = levels[*ind].minimap_draw(
+                                    area.2 + 16 - x1,
+                                    area.1 - y1,
+                                    levels,
+                                    &mut seen,
+                                    *ind,
+                                    &global_state.changed_tiles,
+                                    &global_state.visited,
+                                    false,
+                                );
+                                area = (area.0, area.1, x2.max(area.2), y2.max(area.3));
+                            }
+
+                            // where the player is
+                            if (paused_frames / 15) % 2 == 0 {
+                                let (px, py) = level.focus_position();
+                                draw_rectangle(
+                                    (ox + px / TILE_SIZE * 2 - 1) as f32,
+                                    (oy + py / TILE_SIZE * 2 - 1) as f32,
+                                    4.,
+                                    4.,
+                                    RED,
+                                );
+                            }
+
+                            draw_text_cool_c(&font, "map", SCREEN_WIDTH / 2, 8, WHITE);
+
+                            // what the room's author had to say about it
+                            let mut info = level.name.clone();
+                            if let Some(author) = &level.meta.author {
+                                info = format!("{} - by {}", info, author);
+                            }
+                            if let Some(par) = level.meta.par_time {
+                                info = format!(
+                                    "{} - par {:0>2}:{:0>2}",
+                                    info,
+                                    par / 3600,
+                                    (par / 60) % 60
+                                );
+                            }
+                            draw_text_cool_c(&font, &info, SCREEN_WIDTH / 2, 26, WHITE);
+                            if let Some(description) = &level.meta.description {
+                                draw_tip_text(
+                                    &font,
+                                    description,
+                                    SCREEN_WIDTH / 2 - 200,
+                                    SCREEN_HEIGHT - 48,
+                                    400,
+                                    999,
+                                    WHITE,
+                                );
+                            }
+                            draw_text_cool_c(
+                                &font,
+                                "arrows - scroll   x - back",
+                                SCREEN_WIDTH / 2,
+                                SCREEN_HEIGHT - 20,
+                                WHITE,
+                            );
+                        }
                     }
                 } else {
                     draw_texture_ex(
@@ -2299,7 +3109,7 @@ async fn main() {
                         &numbers,
                         &t,
                         64. + (-192. * (1. - prog)) as i32 as f32,
-                        149.,
+                        113.,
                         BLACK,
                         global_state.timer,
                     );
@@ -2310,7 +3120,7 @@ async fn main() {
                         &numbers,
                         &t,
                         65. + (-192. * (1. - prog)) as i32 as f32,
-                        186.,
+                        150.,
                         color_u8!(79, 6, 79, 255),
                         global_state.timer,
                     );
@@ -2320,15 +3130,35 @@ async fn main() {
                         &numbers,
                         &t,
                         67. + (-192. * (1. - prog)) as i32 as f32,
-                        221.,
+                        185.,
                         color_u8!(79, 6, 6, 255),
                         global_state.timer,
                     );
+                    let t = format!("{}/{}", global_state.coins, coin_count);
+
+                    draw_number_text(
+                        &numbers,
+                        &t,
+                        67. + (-192. * (1. - prog)) as i32 as f32,
+                        220.,
+                        color_u8!(111, 79, 6, 255),
+                        global_state.timer,
+                    );
                     let t = texture_cache!(textures, "assets/pausebottom.png");
                     draw_texture(&t, 0., (150. * (1. - prog)) as i32 as f32, WHITE);
                     let t = texture_cache!(textures, "assets/winrightbase.png");
                     draw_texture(&t, (256. * (1. - prog)) as i32 as f32, 0., WHITE);
 
+                    // hardcore runs get their own line, failed or not
+                    if global_state.default_modifiers.hardcore {
+                        let t = if global_state.game_over {
+                            "hardcore - run over"
+                        } else {
+                            "hardcore - cleared"
+                        };
+                        draw_text_cool_c(&font, t, 500 + (256. * (1. - prog)) as i32, 164, WHITE);
+                    }
+
                     let t = texture_cache!(
                         textures,
                         if paused_selection == 0 {
@@ -2355,6 +3185,11 @@ async fn main() {
                                     "levels/{}",
                                     levelsets[levelset_ind]
                                 ));
+                                current_set = levelsets[levelset_ind]
+                                    .trim_end_matches(pack::PACK_EXT)
+                                    .to_string();
+                                set_stash.clear();
+                                entered_from.clear();
                                 let current_ind = 0; // we assume the first level is index 0
 
                                 let level_raw = levelset.levels[current_ind].clone();
@@ -2372,6 +3207,7 @@ async fn main() {
                                 themes = levelset.themes.clone();
                                 deaths = 0;
                                 secret_count = levelset.secret_count;
+                                coin_count = levelset.coin_count;
                                 transition_ticks = 0;
                                 secret_transition = false;
                                 paused_frames = 0;
@@ -2389,20 +3225,25 @@ async fn main() {
                                 clear_playbacks(&music_ctx, &mut current_musics);
 
                                 // play new music
-                                match &themes[level.theme].mus {
-                                    Some(p) => {
-                                        play_r_sound(&mut current_musics, &rawsounds, &music_ctx, p)
-                                    }
-                                    None => pause_everything(&mut current_musics, &music_ctx),
-                                }
+                                play_room_music(
+                                    &level,
+                                    &themes,
+                                    &mut current_musics,
+                                    &rawsounds,
+                                    &music_ctx,
+                                );
+
+                                let mut global_state = levels::GlobalState::new(
+                                    Some(global_state.default_modifiers),
+                                    global_state.physics,
+                                );
+                                global_state.enter_room(&level, &themes[level.theme]);
 
                                 state = State::Game {
                                     levelset: Some(levelset),
                                     current_ind,
                                     level,
-                                    global_state: levels::GlobalState::new(Some(
-                                        global_state.default_modifiers,
-                                    )),
+                                    global_state,
                                     won: false,
                                 }
                             }
@@ -2410,24 +3251,30 @@ async fn main() {
                             _ => unreachable!(),
                         }
                     }
-                } else if paused && is_key_pressed(KeyCode::Z) {
+                } else if paused && !showing_map && is_key_pressed(KeyCode::Z) {
                     if is_key_pressed(KeyCode::Z) {
                         match paused_selection {
                             0 => {
                                 paused = false;
                                 // play new music
-                                match &themes[level.theme].mus {
-                                    Some(p) => {
-                                        play_r_sound(&mut current_musics, &rawsounds, &music_ctx, p)
-                                    }
-                                    None => pause_everything(&mut current_musics, &music_ctx),
-                                }
+                                play_room_music(
+                                    level,
+                                    &themes,
+                                    &mut current_musics,
+                                    &rawsounds,
+                                    &music_ctx,
+                                );
                             }
                             1 => {
                                 let levelset = levels::load_levelset(&format!(
                                     "levels/{}",
                                     levelsets[levelset_ind]
                                 ));
+                                current_set = levelsets[levelset_ind]
+                                    .trim_end_matches(pack::PACK_EXT)
+                                    .to_string();
+                                set_stash.clear();
+                                entered_from.clear();
                                 let current_ind = 0; // we assume the first level is index 0
 
                                 let level_raw = levelset.levels[current_ind].clone();
@@ -2445,6 +3292,7 @@ async fn main() {
                                 themes = levelset.themes.clone();
                                 deaths = 0;
                                 secret_count = levelset.secret_count;
+                                coin_count = levelset.coin_count;
                                 transition_ticks = 0;
                                 secret_transition = false;
                                 paused_frames = 0;
@@ -2462,20 +3310,25 @@ async fn main() {
                                 clear_playbacks(&music_ctx, &mut current_musics);
 
                                 // play new music
-                                match &themes[level.theme].mus {
-                                    Some(p) => {
-                                        play_r_sound(&mut current_musics, &rawsounds, &music_ctx, p)
-                                    }
-                                    None => pause_everything(&mut current_musics, &music_ctx),
-                                }
+                                play_room_music(
+                                    &level,
+                                    &themes,
+                                    &mut current_musics,
+                                    &rawsounds,
+                                    &music_ctx,
+                                );
+
+                                let mut global_state = levels::GlobalState::new(
+                                    Some(global_state.default_modifiers),
+                                    global_state.physics,
+                                );
+                                global_state.enter_room(&level, &themes[level.theme]);
 
                                 state = State::Game {
                                     levelset: Some(levelset),
                                     current_ind,
                                     level,
-                                    global_state: levels::GlobalState::new(Some(
-                                        global_state.default_modifiers,
-                                    )),
+                                    global_state,
                                     won: false,
                                 }
                             }
@@ -0,0 +1,131 @@
+// levelsets can be shared as a single .nmlpack file, which is a zip of the levelset folder.
+// anything under levels/<name>.nmlpack/ is read out of the zip instead of the disk, so the
+// rest of the game doesn't have to care whether a levelset is packed or not
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::path::Path;
+
+pub const PACK_EXT: &str = ".nmlpack";
+
+thread_local! {
+    // pack path to the files inside it, read in fully the first time the pack is used
+    static PACKS: RefCell<HashMap<String, HashMap<String, Vec<u8>>>> = RefCell::new(HashMap::new());
+}
+
+// "levels/foo.nmlpack/assets/bg.png" -> ("levels/foo.nmlpack", "assets/bg.png")
+fn split(path: &str) -> Option<(&str, &str)> {
+    let i = path.find(".nmlpack/")? + PACK_EXT.len();
+    Some((&path[..i], &path[i + 1..]))
+}
+
+fn open(pack_path: &str) -> HashMap<String, Vec<u8>> {
+    let file = std::fs::File::open(pack_path).expect("pack should exist");
+    // a broken pack just looks empty, so it won't show up in the level select
+    let Ok(mut archive) = zip::ZipArchive::new(file) else {
+        return HashMap::new();
+    };
+
+    let mut files = HashMap::new();
+    for i in 0..archive.len() {
+        let mut f = archive.by_index(i).expect("should be readable");
+        if f.is_dir() {
+            continue;
+        }
+        let mut data = vec![];
+        f.read_to_end(&mut data).expect("should be readable");
+        files.insert(f.name().replace('\\', "/"), data);
+    }
+
+    // zipping the folder itself puts everything under "<name>/", so strip that
+    let root = files
+        .keys()
+        .filter_map(|k| k.strip_suffix("levels.levelset"))
+        .min_by_key(|r| r.len())
+        .unwrap_or("")
+        .to_string();
+    files
+        .into_iter()
+        .filter_map(|(k, v)| Some((k.strip_prefix(&root)?.to_string(), v)))
+        .collect()
+}
+
+fn with_pack<T>(pack_path: &str, f: impl FnOnce(&HashMap<String, Vec<u8>>) -> T) -> T {
+    PACKS.with(|packs| {
+        let mut packs = packs.borrow_mut();
+        f(packs
+            .entry(pack_path.to_string())
+            .or_insert_with(|| open(pack_path)))
+    })
+}
+
+pub fn read(path: &str) -> Option<Vec<u8>> {
+    match split(path) {
+        Some((pack_path, inner)) => with_pack(pack_path, |files| files.get(inner).cloned()),
+        None => std::fs::read(path).ok(),
+    }
+}
+
+pub fn read_to_string(path: &str) -> Option<String> {
+    String::from_utf8(read(path)?).ok()
+}
+
+pub fn exists(path: &str) -> bool {
+    match split(path) {
+        Some((pack_path, inner)) => with_pack(pack_path, |files| files.contains_key(inner)),
+        None => Path::new(path).exists(),
+    }
+}
+
+// themes and levels in a pack name their files relative to the pack. a pack made from a
+// folder keeps the folder's paths, like levels/doublejump/assets/tileset.png, so those are
+// looked up without the levels/<set>/ part. anything the pack doesn't have, like the game's
+// own assets/, is left alone
+pub fn resolve(from: &str, path: &str) -> String {
+    if let Some((pack_path, _)) = split(from) {
+        let in_folder = path
+            .strip_prefix("levels/")
+            .and_then(|p| p.split_once('/'))
+            .map(|(_, p)| p);
+        for p in [Some(path), in_folder].into_iter().flatten() {
+            let packed = format!("{}/{}", pack_path, p);
+            if exists(&packed) {
+                return packed;
+            }
+        }
+    }
+    path.to_string()
+}
+
+// zips up a levelset folder into a pack
+pub fn write(dir: &str, out: &str) {
+    let mut paths = vec![];
+    let mut to_visit = vec![Path::new(dir).to_path_buf()];
+    while let Some(d) = to_visit.pop() {
+        for entry in std::fs::read_dir(&d).expect("should be a directory") {
+            let p = entry.expect("should be readable").path();
+            if p.is_dir() {
+                to_visit.push(p);
+            } else {
+                paths.push(p);
+            }
+        }
+    }
+    paths.sort();
+
+    let file = std::fs::File::create(out).expect("should be able to write pack");
+    let mut zip = zip::ZipWriter::new(file);
+    let options = zip::write::SimpleFileOptions::default();
+    for p in paths {
+        let name = p
+            .strip_prefix(dir)
+            .expect("is inside the folder")
+            .to_string_lossy()
+            .replace('\\', "/");
+        zip.start_file(name, options)
+            .expect("should be able to write pack");
+        zip.write_all(&std::fs::read(&p).expect("should be readable"))
+            .expect("should be able to write pack");
+    }
+    zip.finish().expect("should be able to write pack");
+}
@@ -0,0 +1,417 @@
+// imports rooms from Tiled (https://www.mapeditor.org) maps, either .tmx or .tmj/.json.
+//
+// each tileset tile needs a "tile" property with the name of the tile, like "wall" or
+// "spikesup". tile layers become layers, and objects on object layers are placed by
+// their class (or name if it has none):
+//   player, exit_anchor
+//   door / secretdoor, with a "to" property naming the room it leads to
+//   sign, with a "text" property. pages are split by |
+// map properties are copied into the level header, so "left", "theme", "author", "par"
+// and friends all work. "left" and friends can list several rooms separated by commas
+use crate::levels::{self, LevelRaw};
+use crate::pack;
+use std::collections::HashMap;
+use std::path::Path;
+
+struct TiledObject {
+    kind: String,
+    x: f64,
+    y: f64,
+    properties: Vec<(String, String)>,
+}
+
+struct TiledMap {
+    width: usize,
+    height: usize,
+    tile_width: f64,
+    tile_height: f64,
+    properties: Vec<(String, String)>,
+    // gid to tile name
+    tile_names: HashMap<u32, String>,
+    layers: Vec<Vec<u32>>,
+    objects: Vec<TiledObject>,
+}
+
+// these bits of a gid are for flipping, which we don't care about
+const GID_MASK: u32 = 0x0fffffff;
+
+const LEGEND_CHARS: &str =
+    "#abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789!$%&*+-/<>?@^_~";
+
+pub fn find_map(path_without_ext: &str) -> Option<String> {
+    ["tmx", "tmj", "json"]
+        .iter()
+        .map(|e| format!("{}.{}", path_without_ext, e))
+        .find(|p| pack::exists(p))
+}
+
+pub fn import(path: &str, level_inds: &HashMap<&str, usize>) -> LevelRaw {
+    levels::parse_level(&to_lvl(path), level_inds)
+}
+
+// writes the map out as a v2 .lvl
+pub fn to_lvl(path: &str) -> String {
+    let map = if path.ends_with(".tmx") {
+        read_tmx(path)
+    } else {
+        read_json(path)
+    };
+
+    let prop = |props: &Vec<(String, String)>, name: &str| {
+        props
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.clone())
+    };
+
+    let name = prop(&map.properties, "name").unwrap_or_else(|| {
+        Path::new(path)
+            .file_stem()
+            .expect("should have a file name")
+            .to_string_lossy()
+            .to_string()
+    });
+
+    let mut layers: Vec<Vec<String>> = map
+        .layers
+        .iter()
+        .map(|l| {
+            l.iter()
+                .map(|gid| match gid & GID_MASK {
+                    0 => "empty".to_string(),
+                    gid => map
+                        .tile_names
+                        .get(&gid)
+                        .cloned()
+                        .expect("every tile used should have a \"tile\" property"),
+                })
+                .collect()
+        })
+        .collect();
+
+    // objects go on a layer of their own, on top
+    let mut doors = vec![];
+    let mut signs = vec![];
+    if !map.objects.is_empty() {
+        let mut layer = vec!["empty".to_string(); map.width * map.height];
+        let mut objects: Vec<&TiledObject> = map.objects.iter().collect();
+        // doors and signs get matched up with their text in reading order
+        objects.sort_by_key(|o| {
+            (
+                (o.y / map.tile_height) as usize,
+                (o.x / map.tile_width) as usize,
+            )
+        });
+        for o in objects {
+            let tx = ((o.x / map.tile_width) as usize).min(map.width - 1);
+            let ty = ((o.y / map.tile_height) as usize).min(map.height - 1);
+            match o.kind.as_str() {
+                "door" | "secretdoor" => doors
+                    .push(prop(&o.properties, "to").expect("doors should have a \"to\" property")),
+                "sign" => signs.push(
+                    prop(&o.properties, "text").expect("signs should have a \"text\" property"),
+                ),
+                _ => (),
+            }
+            layer[ty * map.width + tx] = o.kind.clone();
+        }
+        layers.push(layer);
+    }
+
+    let mut legend: Vec<String> = vec!["empty".to_string()];
+    for l in layers.iter() {
+        for t in l.iter() {
+            if !legend.contains(t) {
+                legend.push(t.clone());
+            }
+        }
+    }
+    assert!(
+        legend.len() <= LEGEND_CHARS.len() + 1,
+        "too many different tiles in one map"
+    );
+    let legend: HashMap<&String, char> = legend
+        .iter()
+        .enumerate()
+        .map(|(i, t)| {
+            let c = if i == 0 {
+                '.'
+            } else {
+                LEGEND_CHARS.chars().nth(i - 1).expect("checked above")
+            };
+            (t, c)
+        })
+        .collect();
+
+    let mut out = format!("nmllevel 2\n=== name\n{}\n=== meta\n", name);
+    let mut links = String::new();
+    for (k, v) in map.properties.iter() {
+        match k.as_str() {
+            "author" | "description" | "par" | "music" | "time_limit" => {
+                out.push_str(&format!("{}: {}\n", k, v))
+            }
+            "modifier" | "modifiers" => {
+                for m in v.split(",") {
+                    out.push_str(&format!("modifier: {}\n", m.trim()))
+                }
+            }
+            "left" | "right" | "up" | "down" | "theme" => {
+                for r in v.split(",") {
+                    links.push_str(&format!("{}: {}\n", k, r.trim()))
+                }
+            }
+            _ => (),
+        }
+    }
+    for d in doors {
+        links.push_str(&format!("door: {}\n", d));
+    }
+
+    out.push_str("=== legend\n");
+    let mut sorted: Vec<(&&String, &char)> = legend.iter().collect();
+    sorted.sort_by_key(|(_, c)| LEGEND_CHARS.find(**c));
+    for (t, c) in sorted {
+        out.push_str(&format!("{}: {}\n", c, t));
+    }
+    out.push_str(&format!("=== links\n{}", links));
+    if !signs.is_empty() {
+        out.push_str("=== signs\n");
+        for s in signs {
+            out.push_str(&format!("sign: {}\n", s));
+        }
+    }
+    for l in layers.iter() {
+        out.push_str("=== layer\n");
+        for row in l.chunks(map.width) {
+            let row: String = row.iter().map(|t| legend[t]).collect();
+            out.push_str(&row);
+            out.push('\n');
+        }
+    }
+    out
+}
+
+fn read_tmx(path: &str) -> TiledMap {
+    let contents = pack::read_to_string(path).expect("should be able to read map");
+    let doc = roxmltree::Document::parse(&contents).expect("map should be valid xml");
+    let root = doc.root_element();
+
+    let attr = |n: roxmltree::Node, a: &str| n.attribute(a).unwrap_or("").to_string();
+    let num = |n: roxmltree::Node, a: &str| -> f64 {
+        n.attribute(a)
+            .unwrap_or("0")
+            .parse()
+            .expect("should be a number")
+    };
+    let properties = |n: roxmltree::Node| -> Vec<(String, String)> {
+        n.children()
+            .filter(|c| c.has_tag_name("properties"))
+            .flat_map(|c| c.children().filter(|p| p.has_tag_name("property")))
+            .map(|p| {
+                let value = match p.attribute("value") {
+                    Some(v) => v.to_string(),
+                    None => p.text().unwrap_or("").to_string(),
+                };
+                (attr(p, "name"), value)
+            })
+            .collect()
+    };
+
+    assert!(
+        root.attribute("infinite") != Some("1"),
+        "infinite maps aren't supported"
+    );
+
+    let mut map = TiledMap {
+        width: num(root, "width") as usize,
+        height: num(root, "height") as usize,
+        tile_width: num(root, "tilewidth"),
+        tile_height: num(root, "tileheight"),
+        properties: properties(root),
+        tile_names: HashMap::new(),
+        layers: vec![],
+        objects: vec![],
+    };
+
+    for tileset in root.children().filter(|c| c.has_tag_name("tileset")) {
+        let first_gid = num(tileset, "firstgid") as u32;
+        let external;
+        let tileset = match tileset.attribute("source") {
+            Some(source) => {
+                let p = Path::new(path)
+                    .parent()
+                    .expect("should have a parent")
+                    .join(source);
+                external = pack::read_to_string(&p.to_string_lossy())
+                    .expect("should be able to read tileset");
+                roxmltree::Document::parse(&external).expect("tileset should be valid xml")
+            }
+            None => roxmltree::Document::parse(&contents[tileset.range()])
+                .expect("tileset should be valid xml"),
+        };
+        for tile in tileset
+            .root_element()
+            .children()
+            .filter(|c| c.has_tag_name("tile"))
+        {
+            if let Some((_, name)) = properties(tile).into_iter().find(|(n, _)| n == "tile") {
+                map.tile_names
+                    .insert(first_gid + num(tile, "id") as u32, name);
+            }
+        }
+    }
+
+    // groups can hold more layers
+    let mut stack: Vec<roxmltree::Node> = root.children().rev().collect();
+    while let Some(node) = stack.pop() {
+        if node.has_tag_name("group") {
+            stack.extend(node.children().rev());
+        } else if node.has_tag_name("layer") {
+            let data = node
+                .children()
+                .find(|c| c.has_tag_name("data"))
+                .expect("layer should have data");
+            assert!(
+                data.attribute("encoding") == Some("csv"),
+                "only csv layer data is supported"
+            );
+            map.layers.push(
+                data.text()
+                    .unwrap_or("")
+                    .split(",")
+                    .map(|g| g.trim().parse().expect("should be a gid"))
+                    .collect(),
+            );
+        } else if node.has_tag_name("objectgroup") {
+            for o in node.children().filter(|c| c.has_tag_name("object")) {
+                let kind = [attr(o, "class"), attr(o, "type"), attr(o, "name")]
+                    .into_iter()
+                    .find(|k| !k.is_empty())
+                    .unwrap_or_default();
+                // tile objects sit on their bottom edge
+                let y = if o.attribute("gid").is_some() {
+                    num(o, "y") - map.tile_height
+                } else {
+                    num(o, "y")
+                };
+                map.objects.push(TiledObject {
+                    kind,
+                    x: num(o, "x"),
+                    y,
+                    properties: properties(o),
+                });
+            }
+        }
+    }
+
+    map
+}
+
+fn read_json(path: &str) -> TiledMap {
+    let contents = pack::read_to_string(path).expect("should be able to read map");
+    let root: serde_json::Value =
+        serde_json::from_str(&contents).expect("map should be valid json");
+
+    let num = |v: &serde_json::Value, a: &str| v[a].as_f64().unwrap_or(0.);
+    let text = |v: &serde_json::Value, a: &str| v[a].as_str().unwrap_or("").to_string();
+    let properties = |v: &serde_json::Value| -> Vec<(String, String)> {
+        v["properties"]
+            .as_array()
+            .map(|a| {
+                a.iter()
+                    .map(|p| {
+                        let value = match &p["value"] {
+                            serde_json::Value::String(s) => s.clone(),
+                            other => other.to_string(),
+                        };
+                        (text(p, "name"), value)
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+
+    assert!(
+        root["infinite"].as_bool() != Some(true),
+        "infinite maps aren't supported"
+    );
+
+    let mut map = TiledMap {
+        width: num(&root, "width") as usize,
+        height: num(&root, "height") as usize,
+        tile_width: num(&root, "tilewidth"),
+        tile_height: num(&root, "tileheight"),
+        properties: properties(&root),
+        tile_names: HashMap::new(),
+        layers: vec![],
+        objects: vec![],
+    };
+
+    for tileset in root["tilesets"].as_array().unwrap_or(&vec![]) {
+        let first_gid = num(tileset, "firstgid") as u32;
+        let tileset = match tileset["source"].as_str() {
+            Some(source) => {
+                let p = Path::new(path)
+                    .parent()
+                    .expect("should have a parent")
+                    .join(source);
+                let external = pack::read_to_string(&p.to_string_lossy())
+                    .expect("should be able to read tileset");
+                serde_json::from_str(&external).expect("tileset should be valid json")
+            }
+            None => tileset.clone(),
+        };
+        for tile in tileset["tiles"].as_array().unwrap_or(&vec![]) {
+            if let Some((_, name)) = properties(tile).into_iter().find(|(n, _)| n == "tile") {
+                map.tile_names
+                    .insert(first_gid + num(tile, "id") as u32, name);
+            }
+        }
+    }
+
+    // groups can hold more layers
+    let mut stack: Vec<&serde_json::Value> = root["layers"]
+        .as_array()
+        .map(|l| l.iter().rev().collect())
+        .unwrap_or_default();
+    while let Some(layer) = stack.pop() {
+        match layer["type"].as_str() {
+            Some("group") => {
+                if let Some(l) = layer["layers"].as_array() {
+                    stack.extend(l.iter().rev());
+                }
+            }
+            Some("tilelayer") => map.layers.push(
+                layer["data"]
+                    .as_array()
+                    .expect("only csv layer data is supported")
+                    .iter()
+                    .map(|g| g.as_u64().expect("should be a gid") as u32)
+                    .collect(),
+            ),
+            Some("objectgroup") => {
+                for o in layer["objects"].as_array().unwrap_or(&vec![]) {
+                    let kind = [text(o, "class"), text(o, "type"), text(o, "name")]
+                        .into_iter()
+                        .find(|k| !k.is_empty())
+                        .unwrap_or_default();
+                    // tile objects sit on their bottom edge
+                    let y = if o["gid"].is_number() {
+                        num(o, "y") - map.tile_height
+                    } else {
+                        num(o, "y")
+                    };
+                    map.objects.push(TiledObject {
+                        kind,
+                        x: num(o, "x"),
+                        y,
+                        properties: properties(o),
+                    });
+                }
+            }
+            _ => (),
+        }
+    }
+
+    map
+}